@@ -1,16 +1,27 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
+use gmeta::{InOut, Metadata, Out};
 use gstd::{prelude::*, ActorId};
 
 pub type TxId = u64;
 pub type ValidUntil = u64;
+
+/// Metadata of the fungible token program.
+///
+/// An [`InitConfig`] that fails validation aborts the deployment with the
+/// panic message `"Invalid InitConfig: {error:?}"`, where `error` is the
+/// `Debug` text of the [`FTError`] variant, e.g. `Invalid InitConfig: NameError`.
+///
+/// `Handle` replies with `Result<FTReply, FTError>` except for
+/// [`FTAction::BalanceOf`], which replies with [`legacy::FTEvent::Balance`]
+/// like the legacy token did, and for the [`legacy::FTAction`] messages,
+/// which reply with a [`legacy::FTEvent`].
 pub struct FungibleTokenMetadata;
 
 impl Metadata for FungibleTokenMetadata {
     type Init = InOut<InitConfig, FTReply>;
     type Handle = InOut<FTAction, Result<FTReply, FTError>>;
-    type Others = ();
+    type Others = Out<FTEvent>;
     type Reply = ();
     type Signal = ();
     type State = InOut<Query, QueryReply>;
@@ -36,6 +47,7 @@ pub struct InitConfig {
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub struct ExternalLinks {
+    /// Token logo; may be left empty.
     pub image: String,
     pub website: Option<String>,
     pub telegram: Option<String>,
@@ -44,12 +56,69 @@ pub struct ExternalLinks {
     pub tokenomics: Option<String>,
 }
 
+/// A single field of [`ExternalLinks`] with its new value.
+#[derive(Debug, Decode, Encode, TypeInfo, Clone)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum ExternalLink {
+    Image(String),
+    Website(Option<String>),
+    Telegram(Option<String>),
+    Twitter(Option<String>),
+    Discord(Option<String>),
+    Tokenomics(Option<String>),
+}
+
 #[derive(Debug, Decode, Encode, TypeInfo, Default, Clone)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub struct Config {
     pub tx_storage_period: u64,
     pub tx_payment: u128,
+    /// Enables `Deposit`/`Withdraw`, turning the token into wrapped native VARA.
+    ///
+    /// Every token is then backed by value held by the program, so `Mint`,
+    /// `FlashMint`, `Burn`, `AdminBurnFrom` and a non-zero `initial_supply`
+    /// are rejected, and value attached to anything but `Deposit` is refunded.
+    pub native_wrapper: bool,
+    /// Flash mint fee in basis points of the borrowed amount.
+    pub flash_mint_fee_bps: u16,
+    /// Account credited with flash mint fees; fees are burnt if it is zero.
+    pub flash_mint_fee_collector: ActorId,
+    pub limits: TransferLimits,
+    pub supply_policy: SupplyPolicy,
+}
+
+/// How `total_supply` caps minting and reacts to burns.
+///
+/// Wrapped native value moves `total_supply` together with `current_supply`
+/// and is not limited by the policy.
+#[derive(Debug, Decode, Encode, TypeInfo, Default, Clone, Copy, PartialEq, Eq)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SupplyPolicy {
+    /// `total_supply` is a fixed cap; burnt tokens can be minted again.
+    #[default]
+    FixedCap,
+    /// Burning lowers `total_supply`, permanently shrinking the cap.
+    BurnReducesCap,
+    /// Minting is unlimited and `total_supply` follows the largest supply reached.
+    Uncapped,
+}
+
+/// Anti-whale limits applied to transfers between accounts.
+///
+/// Accounts in the admin-managed exemption list bypass the limits.
+#[derive(Debug, Decode, Encode, TypeInfo, Default, Clone)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct TransferLimits {
+    /// Largest amount a single transfer may move.
+    pub max_transfer_amount: Option<u128>,
+    /// Largest balance a receiving account may reach.
+    pub max_wallet_balance: Option<u128>,
+    /// Blocks an account must wait between its transfers; zero disables it.
+    pub transfer_cooldown: u32,
 }
 
 #[derive(Debug, Decode, Encode, TypeInfo)]
@@ -78,6 +147,9 @@ pub enum FTAction {
         to: ActorId,
         amount: u128,
     },
+    /// Shares its encoding with [`legacy::FTAction::BalanceOf`], so it is
+    /// always answered with [`legacy::FTEvent::Balance`]; use
+    /// [`FTAction::GetBalance`] for an [`FTReply::Balance`].
     BalanceOf(ActorId),
     AddAdmin {
         admin_id: ActorId,
@@ -85,9 +157,70 @@ pub enum FTAction {
     DeleteAdmin {
         admin_id: ActorId,
     },
+    /// Mints tokens equal to the attached value to the sender.
+    Deposit,
+    /// Burns `amount` tokens of the sender and sends the same native value back.
+    Withdraw {
+        amount: u128,
+    },
+    /// Mints `amount` to `receiver` within the supply cap, sends it a
+    /// [`FlashMintCallback`] and burns `amount` plus the fee from it once it
+    /// accepts the loan. The borrowed amount cannot be spent until then.
+    FlashMint {
+        receiver: ActorId,
+        amount: u128,
+        payload: Vec<u8>,
+    },
+    SetLimits {
+        limits: TransferLimits,
+    },
+    AddLimitExemption {
+        account: ActorId,
+    },
+    RemoveLimitExemption {
+        account: ActorId,
+    },
+    /// Burns `amount` tokens of `from` using the allowance `from` gave the admin.
+    AdminBurnFrom {
+        from: ActorId,
+        amount: u128,
+    },
+    UpdateDescription {
+        description: String,
+    },
+    UpdateExternalLink {
+        link: ExternalLink,
+    },
+    /// Freezes the description and external links permanently.
+    LockMetadata,
+    GetBalance(ActorId),
+}
+
+/// Callback sent to the receiver of a flash mint.
+///
+/// The receiver repays by replying [`FlashMintCallbackReply::Accepted`] while
+/// holding `amount + fee` tokens and allowing the token program to spend them,
+/// as in ERC-3156. Otherwise only the locked `amount` is burnt, nothing else
+/// is charged and the flash mint fails.
+#[derive(Debug, Decode, Encode, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct FlashMintCallback {
+    pub initiator: ActorId,
+    pub amount: u128,
+    pub fee: u128,
+    pub payload: Vec<u8>,
 }
 
-#[derive(Debug, Encode, Decode, TypeInfo)]
+/// Reply of a flash mint receiver consenting to repay the loan.
+#[derive(Debug, Decode, Encode, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum FlashMintCallbackReply {
+    Accepted,
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub enum FTReply {
@@ -114,6 +247,75 @@ pub enum FTReply {
         admin_id: ActorId,
     },
     Balance(u128),
+    Deposited {
+        to: ActorId,
+        amount: u128,
+    },
+    Withdrawn {
+        from: ActorId,
+        amount: u128,
+    },
+    FlashMinted {
+        receiver: ActorId,
+        amount: u128,
+        fee: u128,
+    },
+    LimitsSet {
+        limits: TransferLimits,
+    },
+    LimitExemptionAdded {
+        account: ActorId,
+    },
+    LimitExemptionRemoved {
+        account: ActorId,
+    },
+    DescriptionUpdated {
+        description: String,
+    },
+    ExternalLinkUpdated {
+        link: ExternalLink,
+    },
+    MetadataLocked,
+}
+
+/// State change published to indexers by sending it to the zero address.
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum FTEvent {
+    /// Mints come from and burns go to the zero address.
+    Transferred {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+    Approved {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+    AdminAdded {
+        admin_id: ActorId,
+    },
+    AdminRemoved {
+        admin_id: ActorId,
+    },
+    LimitsSet {
+        limits: TransferLimits,
+    },
+    LimitExemptionAdded {
+        account: ActorId,
+    },
+    LimitExemptionRemoved {
+        account: ActorId,
+    },
+    DescriptionUpdated {
+        description: String,
+    },
+    ExternalLinkUpdated {
+        link: ExternalLink,
+    },
+    MetadataLocked,
 }
 
 #[derive(Debug, Encode, Decode, TypeInfo, MaxEncodedLen, Clone)]
@@ -131,6 +333,24 @@ pub enum FTError {
     AdminAlreadyExists,
     CantDeleteYourself,
     TxAlreadyExists,
+    NotNativeWrapper,
+    /// Wrapped native tokens can only be issued against deposited value.
+    UnbackedSupply,
+    /// Wrapped native tokens leave the supply through `Withdraw` only.
+    WrappedBurn,
+    FlashMintInProgress,
+    FlashMintNotRepaid,
+    /// Flash mints can only be sent to programs other than the token itself.
+    InvalidFlashMintReceiver,
+    MaxTransferAmountExceeded,
+    MaxWalletBalanceExceeded,
+    TransferCooldown,
+    ArithmeticOverflow,
+    NameError,
+    SymbolError,
+    ExternalLinksError,
+    CapacityError,
+    MetadataLocked,
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -157,6 +377,17 @@ pub enum Query {
     GetTxIdsForAccount {
         account: ActorId,
     },
+    GetTxRecord {
+        account: ActorId,
+        tx_id: TxId,
+    },
+    Holders {
+        offset: u32,
+        limit: u32,
+    },
+    HolderCount,
+    Limits,
+    MetadataLocked,
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -174,5 +405,71 @@ pub enum QueryReply {
     AllowanceOfAccount(u128),
     Admins(Vec<ActorId>),
     TxValidityTime(ValidUntil),
-    TxIdsForAccount { tx_ids: Vec<TxId> },
+    TxIdsForAccount {
+        tx_ids: Vec<TxId>,
+    },
+    TxRecord(Option<TxRecord>),
+    Holders(Vec<(ActorId, u128)>),
+    HolderCount(u32),
+    Limits {
+        limits: TransferLimits,
+        exempt_accounts: Vec<ActorId>,
+    },
+    MetadataLocked(bool),
+}
+
+/// Outcome of an executed transaction, successful or not, kept for deduplication.
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct TxRecord {
+    pub valid_until: ValidUntil,
+    pub reply: Result<FTReply, FTError>,
+}
+
+/// Message format of the token used by the escrow, gDAO and delayed messages
+/// templates, still accepted by `handle` next to [`FTAction`].
+///
+/// Its `Mint` is only served to admins, so a program minting through it, like
+/// the delayed messages template, must first be added with
+/// [`crate::FTAction::AddAdmin`].
+pub mod legacy {
+    use gstd::{prelude::*, ActorId};
+
+    #[derive(Debug, Decode, Encode, TypeInfo)]
+    #[codec(crate = gstd::codec)]
+    #[scale_info(crate = gstd::scale_info)]
+    pub enum FTAction {
+        Mint(u128),
+        Burn(u128),
+        Transfer {
+            from: ActorId,
+            to: ActorId,
+            amount: u128,
+        },
+        Approve {
+            to: ActorId,
+            amount: u128,
+        },
+        TotalSupply,
+        BalanceOf(ActorId),
+    }
+
+    #[derive(Debug, Encode, Decode, TypeInfo)]
+    #[codec(crate = gstd::codec)]
+    #[scale_info(crate = gstd::scale_info)]
+    pub enum FTEvent {
+        Transfer {
+            from: ActorId,
+            to: ActorId,
+            amount: u128,
+        },
+        Approve {
+            from: ActorId,
+            to: ActorId,
+            amount: u128,
+        },
+        TotalSupply(u128),
+        Balance(u128),
+    }
 }
@@ -0,0 +1,43 @@
+//! Test program that forwards every message to the token it was initialized
+//! with and replies with the token's reply, so that tests can talk to the
+//! token from a program instead of a user account. It accepts every flash
+//! mint of the token.
+
+#![no_std]
+
+#[cfg(feature = "std")]
+mod code {
+    include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+}
+
+#[cfg(feature = "std")]
+pub use code::WASM_BINARY_OPT as WASM_BINARY;
+
+#[cfg(not(feature = "std"))]
+mod wasm {
+    use fungible_token_io::FlashMintCallbackReply;
+    use gstd::{msg, ActorId};
+
+    static mut TOKEN: ActorId = ActorId::new([0u8; 32]);
+
+    #[no_mangle]
+    extern "C" fn init() {
+        let token: ActorId = msg::load().expect("Unable to decode the token id");
+        unsafe { TOKEN = token };
+    }
+
+    #[gstd::async_main]
+    async fn main() {
+        // Flash mint callbacks are the only messages the token sends here.
+        if msg::source() == unsafe { TOKEN } {
+            msg::reply(FlashMintCallbackReply::Accepted, 0).expect("Unable to reply");
+            return;
+        }
+        let payload = msg::load_bytes().expect("Unable to load the payload");
+        let reply = msg::send_bytes_for_reply(unsafe { TOKEN }, payload, 0, 0)
+            .expect("Unable to send the payload to the token")
+            .await
+            .expect("The token replied with an error");
+        msg::reply_bytes(reply, 0).expect("Unable to reply");
+    }
+}
@@ -2,6 +2,7 @@
 
 use fungible_token_io::*;
 use gstd::{
+    codec::DecodeAll,
     collections::{hash_map::Entry, HashMap, HashSet},
     exec, msg,
     prelude::*,
@@ -9,6 +10,18 @@ use gstd::{
 };
 
 const ZERO_ID: ActorId = ActorId::new([0u8; 32]);
+/// Number of blocks a flash mint receiver has to reply to the callback.
+const FLASH_MINT_TIMEOUT: u32 = 10;
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_SYMBOL_LENGTH: usize = 16;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+const MAX_URL_LENGTH: usize = 256;
+/// `10^decimals` has to fit in `u128`.
+const MAX_DECIMALS: u8 = 38;
+const MAX_INITIAL_CAPACITY: u32 = 10_000;
+
+#[cfg(test)]
+extern crate std;
 
 #[cfg(test)]
 mod tests;
@@ -33,13 +46,22 @@ struct FungibleToken {
     balances: HashMap<ActorId, u128>,
     /// Map to hold allowance information of token holders.
     allowances: HashMap<ActorId, HashMap<ActorId, u128>>,
-    /// Mapping of executed transactions to the time they are valid.
-    tx_ids: HashMap<(ActorId, TxId), ValidUntil>,
+    /// Mapping of executed transactions to their replies and the time they are valid.
+    tx_ids: HashMap<(ActorId, TxId), TxRecord>,
     /// Mapping of accounts to their transaction IDs.
     account_to_tx_ids: HashMap<ActorId, HashSet<TxId>>,
     /// Configuration parameters for the fungible token contract.
     config: Config,
     admins: Vec<ActorId>,
+    /// Amounts lent to receivers whose flash mint awaits repayment; they stay
+    /// locked so that the loan can always be burnt back.
+    flash_loans: HashMap<ActorId, u128>,
+    /// Accounts that bypass `config.limits`.
+    limit_exempt: HashSet<ActorId>,
+    /// Block height of the last transfer made by each account.
+    last_transfer_block: HashMap<ActorId, u32>,
+    /// Whether `description` and `external_links` are frozen.
+    metadata_locked: bool,
 }
 
 static mut FUNGIBLE_TOKEN: Option<FungibleToken> = None;
@@ -53,17 +75,30 @@ impl FungibleToken {
         let source = msg::source();
         assert!(self.admins.contains(&source), "Not admin");
 
-        self.check_balance(&source, amount * to_users.len() as u128)?;
+        let total_amount = amount
+            .checked_mul(to_users.len() as u128)
+            .ok_or(FTError::ArithmeticOverflow)?;
+        self.check_balance(&source, total_amount)?;
+        self.check_sender_limits(&source, amount)?;
+        // A recipient listed several times is credited once per entry.
+        let mut credits: HashMap<ActorId, u128> = HashMap::new();
+        for to in &to_users {
+            *credits.entry(*to).or_default() += amount;
+        }
+        for (to, credit) in &credits {
+            self.check_wallet_balance(to, *credit)?;
+        }
 
         for to in to_users.clone() {
-            self.balances
-                .entry(source)
-                .and_modify(|balance| *balance -= amount);
-            self.balances
-                .entry(to)
-                .and_modify(|balance| *balance += amount)
-                .or_insert(amount);
+            self.decrease_balance(&source, amount)?;
+            self.increase_balance(&to, amount)?;
+            emit(FTEvent::Transferred {
+                from: source,
+                to,
+                amount,
+            });
         }
+        self.record_transfer(&source);
 
         Ok(FTReply::TransferredToUsers {
             from: source,
@@ -74,39 +109,271 @@ impl FungibleToken {
 
     fn mint(&mut self, amount: u128, to: ActorId) -> Result<FTReply, FTError> {
         assert!(self.admins.contains(&msg::source()), "Not admin");
+        if self.config.native_wrapper {
+            return Err(FTError::UnbackedSupply);
+        }
+        self.check_wallet_balance(&to, amount)?;
 
-        if self.total_supply >= self.current_supply + amount {
-            self.balances
-                .entry(to)
-                .and_modify(|balance| *balance += amount)
-                .or_insert(amount);
-            self.current_supply += amount;
+        self.mint_supply(amount)?;
+        self.increase_balance(&to, amount)?;
+        emit(FTEvent::Transferred {
+            from: ZERO_ID,
+            to,
+            amount,
+        });
 
-            return Ok(FTReply::Transferred {
-                from: ZERO_ID,
-                to,
-                amount,
-            });
-        } else {
-            return Err(FTError::MaxSupplyReached);
-        };
+        Ok(FTReply::Transferred {
+            from: ZERO_ID,
+            to,
+            amount,
+        })
     }
 
     fn burn(&mut self, amount: u128) -> Result<FTReply, FTError> {
+        if self.config.native_wrapper {
+            return Err(FTError::WrappedBurn);
+        }
         let source = msg::source();
-        if self.balances.get(&source).unwrap_or(&0) < &amount {
-            return Err(FTError::NotEnoughBalance);
+        self.decrease_balance(&source, amount)?;
+        self.burn_supply(amount)?;
+        emit(FTEvent::Transferred {
+            from: source,
+            to: ZERO_ID,
+            amount,
+        });
+
+        Ok(FTReply::Transferred {
+            from: source,
+            to: ZERO_ID,
+            amount,
+        })
+    }
+
+    fn admin_burn_from(&mut self, from: &ActorId, amount: u128) -> Result<FTReply, FTError> {
+        let source = msg::source();
+        if !self.admins.contains(&source) {
+            return Err(FTError::NotAdmin);
         }
-        self.balances
-            .entry(source)
-            .and_modify(|balance| *balance -= amount);
-        self.current_supply -= amount;
-        self.total_supply -= amount;
+        if self.config.native_wrapper {
+            return Err(FTError::WrappedBurn);
+        }
+        self.check_balance(from, amount)?;
+        self.can_transfer(&source, from, amount)?;
+
+        self.decrease_balance(from, amount)?;
+        self.burn_supply(amount)?;
+        emit(FTEvent::Transferred {
+            from: *from,
+            to: ZERO_ID,
+            amount,
+        });
 
         Ok(FTReply::Transferred {
+            from: *from,
+            to: ZERO_ID,
+            amount,
+        })
+    }
+
+    /// Adds `amount` to `current_supply` within the limits of `config.supply_policy`.
+    fn mint_supply(&mut self, amount: u128) -> Result<(), FTError> {
+        let current_supply = self
+            .current_supply
+            .checked_add(amount)
+            .ok_or(FTError::MaxSupplyReached)?;
+        if current_supply > self.total_supply {
+            if self.config.supply_policy != SupplyPolicy::Uncapped {
+                return Err(FTError::MaxSupplyReached);
+            }
+            self.total_supply = current_supply;
+        }
+        self.current_supply = current_supply;
+        Ok(())
+    }
+
+    /// Removes `amount` from `current_supply`, lowering the cap as well under
+    /// [`SupplyPolicy::BurnReducesCap`].
+    fn burn_supply(&mut self, amount: u128) -> Result<(), FTError> {
+        self.current_supply = self
+            .current_supply
+            .checked_sub(amount)
+            .ok_or(FTError::ArithmeticOverflow)?;
+        if self.config.supply_policy == SupplyPolicy::BurnReducesCap {
+            self.total_supply = self
+                .total_supply
+                .checked_sub(amount)
+                .ok_or(FTError::ArithmeticOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Grows `current_supply` and `total_supply` alike, for tokens that are
+    /// issued outside of the cap.
+    fn expand_supply(&mut self, amount: u128) -> Result<(), FTError> {
+        let current_supply = self.current_supply.checked_add(amount);
+        let total_supply = self.total_supply.checked_add(amount);
+        let (Some(current_supply), Some(total_supply)) = (current_supply, total_supply) else {
+            return Err(FTError::ArithmeticOverflow);
+        };
+        self.current_supply = current_supply;
+        self.total_supply = total_supply;
+        Ok(())
+    }
+
+    /// Reverts [`Self::expand_supply`].
+    fn contract_supply(&mut self, amount: u128) -> Result<(), FTError> {
+        let current_supply = self.current_supply.checked_sub(amount);
+        let total_supply = self.total_supply.checked_sub(amount);
+        let (Some(current_supply), Some(total_supply)) = (current_supply, total_supply) else {
+            return Err(FTError::ArithmeticOverflow);
+        };
+        self.current_supply = current_supply;
+        self.total_supply = total_supply;
+        Ok(())
+    }
+
+    /// Wraps the attached native value into tokens.
+    ///
+    /// Deposits grow `total_supply` together with `current_supply`, so the
+    /// headroom left for `mint` is not consumed by wrapped value.
+    fn deposit(&mut self) -> Result<FTReply, FTError> {
+        if !self.config.native_wrapper {
+            return Err(FTError::NotNativeWrapper);
+        }
+        let source = msg::source();
+        let amount = msg::value();
+        self.check_wallet_balance(&source, amount)?;
+
+        self.expand_supply(amount)?;
+        self.increase_balance(&source, amount)?;
+        emit(FTEvent::Transferred {
+            from: ZERO_ID,
+            to: source,
+            amount,
+        });
+
+        Ok(FTReply::Deposited { to: source, amount })
+    }
+
+    /// Unwraps `amount` tokens; the native value is attached to the reply.
+    fn withdraw(&mut self, amount: u128) -> Result<FTReply, FTError> {
+        if !self.config.native_wrapper {
+            return Err(FTError::NotNativeWrapper);
+        }
+        let source = msg::source();
+        self.check_balance(&source, amount)?;
+
+        self.decrease_balance(&source, amount)?;
+        self.contract_supply(amount)?;
+        emit(FTEvent::Transferred {
+            from: source,
+            to: ZERO_ID,
+            amount,
+        });
+
+        Ok(FTReply::Withdrawn {
             from: source,
+            amount,
+        })
+    }
+
+    /// Lends freshly minted tokens to `receiver` for the duration of a callback.
+    ///
+    /// The borrowed amount is locked on the receiver until the loan settles, so
+    /// it is burnt in full whether or not the fee is paid. The fee is only taken
+    /// if the receiver accepts the loan and allows this program to spend
+    /// `amount + fee`, so no account is charged without its consent. Only
+    /// programs can reply to the callback; a user account receiver always
+    /// times out, and the origin of the message is rejected upfront.
+    async fn flash_mint(
+        &mut self,
+        receiver: ActorId,
+        amount: u128,
+        payload: Vec<u8>,
+    ) -> Result<FTReply, FTError> {
+        if self.config.native_wrapper {
+            return Err(FTError::UnbackedSupply);
+        }
+        if receiver == ZERO_ID {
+            return Err(FTError::ZeroAddress);
+        }
+        if receiver == exec::origin() || receiver == exec::program_id() {
+            return Err(FTError::InvalidFlashMintReceiver);
+        }
+        if self.flash_loans.contains_key(&receiver) {
+            return Err(FTError::FlashMintInProgress);
+        }
+        let fee = amount
+            .checked_mul(self.config.flash_mint_fee_bps as u128)
+            .map(|fee| fee / 10_000);
+        let Some(fee) = fee.filter(|fee| amount.checked_add(*fee).is_some()) else {
+            return Err(FTError::ArithmeticOverflow);
+        };
+        self.check_wallet_balance(&receiver, amount)?;
+        // The fee is credited after the callback, so the collector is checked
+        // against its balance at the start of the flash mint.
+        let fee_collector = self.config.flash_mint_fee_collector;
+        if fee_collector != ZERO_ID {
+            self.check_wallet_balance(&fee_collector, fee)?;
+        }
+        self.mint_supply(amount)?;
+        self.increase_balance(&receiver, amount)?;
+        self.flash_loans.insert(receiver, amount);
+        emit(FTEvent::Transferred {
+            from: ZERO_ID,
+            to: receiver,
+            amount,
+        });
+
+        let callback = FlashMintCallback {
+            initiator: msg::source(),
+            amount,
+            fee,
+            payload,
+        };
+        let accepted =
+            match msg::send_for_reply_as::<_, FlashMintCallbackReply>(receiver, callback, 0, 0)
+                .and_then(|future| future.up_to(Some(FLASH_MINT_TIMEOUT)))
+            {
+                Ok(future) => matches!(future.await, Ok(FlashMintCallbackReply::Accepted)),
+                Err(_) => false,
+            };
+        self.flash_loans.remove(&receiver);
+
+        let balance = *self.balances.get(&receiver).unwrap_or(&0);
+        let repaid = accepted
+            && balance >= amount + fee
+            && self
+                .can_transfer(&exec::program_id(), &receiver, amount + fee)
+                .is_ok();
+        self.decrease_balance(&receiver, if repaid { amount + fee } else { amount })?;
+        self.current_supply = self
+            .current_supply
+            .checked_sub(amount)
+            .ok_or(FTError::ArithmeticOverflow)?;
+        emit(FTEvent::Transferred {
+            from: receiver,
             to: ZERO_ID,
             amount,
+        });
+        if !repaid {
+            return Err(FTError::FlashMintNotRepaid);
+        }
+
+        if fee_collector == ZERO_ID {
+            self.burn_supply(fee)?;
+        } else {
+            self.increase_balance(&fee_collector, fee)?;
+        }
+        emit(FTEvent::Transferred {
+            from: receiver,
+            to: fee_collector,
+            amount: fee,
+        });
+        Ok(FTReply::FlashMinted {
+            receiver,
+            amount,
+            fee,
         })
     }
 
@@ -119,6 +386,9 @@ impl FungibleToken {
             return Err(FTError::AdminAlreadyExists);
         }
         self.admins.push(*admin_id);
+        emit(FTEvent::AdminAdded {
+            admin_id: *admin_id,
+        });
         Ok(FTReply::AdminAdded {
             admin_id: *admin_id,
         })
@@ -135,6 +405,9 @@ impl FungibleToken {
         }
 
         self.admins.retain(|acc| acc != admin_id);
+        emit(FTEvent::AdminRemoved {
+            admin_id: *admin_id,
+        });
         Ok(FTReply::AdminRemoved {
             admin_id: *admin_id,
         })
@@ -146,34 +419,33 @@ impl FungibleToken {
         to: &ActorId,
         amount: u128,
     ) -> Result<FTReply, FTError> {
-        let msg_source = msg::source();
-        let block_timestamp = exec::block_timestamp();
-        if let Some(tx_id) = tx_id {
-            self.clear_outdated_tx_ids(&msg_source, block_timestamp);
-            self.check_tx_id(tx_id, &msg_source)?;
-        }
+        self.deduplicate(tx_id, |ft| ft.execute_transfer(from, to, amount))
+    }
 
+    fn execute_transfer(
+        &mut self,
+        from: &ActorId,
+        to: &ActorId,
+        amount: u128,
+    ) -> Result<FTReply, FTError> {
         if *from == ActorId::zero() || *to == ActorId::zero() {
             return Err(FTError::ZeroAddress);
         };
 
         self.check_balance(from, amount)?;
 
-        self.can_transfer(&msg_source, from, amount)?;
+        self.check_limits(from, to, amount)?;
 
-        self.balances
-            .entry(*from)
-            .and_modify(|balance| *balance -= amount);
-        self.balances
-            .entry(*to)
-            .and_modify(|balance| *balance += amount)
-            .or_insert(amount);
+        self.can_transfer(&msg::source(), from, amount)?;
 
-        self.set_tx_id_status(
-            tx_id,
-            &msg_source,
-            block_timestamp + self.config.tx_storage_period,
-        );
+        self.decrease_balance(from, amount)?;
+        self.increase_balance(to, amount)?;
+        self.record_transfer(from);
+        emit(FTEvent::Transferred {
+            from: *from,
+            to: *to,
+            amount,
+        });
 
         Ok(FTReply::Transferred {
             from: *from,
@@ -189,24 +461,24 @@ impl FungibleToken {
         to: &ActorId,
         amount: u128,
     ) -> Result<FTReply, FTError> {
+        self.deduplicate(tx_id, |ft| ft.execute_approve(to, amount))
+    }
+
+    fn execute_approve(&mut self, to: &ActorId, amount: u128) -> Result<FTReply, FTError> {
         if *to == ActorId::zero() {
             return Err(FTError::ZeroAddress);
         }
         let msg_source = msg::source();
-        let block_timestamp = exec::block_timestamp();
-        if let Some(tx_id) = tx_id {
-            self.clear_outdated_tx_ids(&msg_source, block_timestamp);
-            self.check_tx_id(tx_id, &msg_source)?;
-        }
         self.allowances
             .entry(msg_source)
             .or_default()
             .insert(*to, amount);
-        self.set_tx_id_status(
-            tx_id,
-            &msg_source,
-            block_timestamp + self.config.tx_storage_period,
-        );
+        emit(FTEvent::Approved {
+            from: msg_source,
+            to: *to,
+            amount,
+        });
+
         Ok(FTReply::Approved {
             from: msg_source,
             to: *to,
@@ -214,13 +486,237 @@ impl FungibleToken {
         })
     }
 
+    /// Runs `action` at most once per `tx_id` of the sender and records its
+    /// outcome, so that a retry gets the original reply even if it was an error.
+    fn deduplicate(
+        &mut self,
+        tx_id: Option<TxId>,
+        action: impl FnOnce(&mut Self) -> Result<FTReply, FTError>,
+    ) -> Result<FTReply, FTError> {
+        let msg_source = msg::source();
+        let block_timestamp = exec::block_timestamp();
+        if let Some(reply) = self.executed_tx_reply(tx_id, &msg_source, block_timestamp) {
+            return reply;
+        }
+        let reply = action(self);
+        self.set_tx_id_status(tx_id, &msg_source, block_timestamp, &reply);
+        reply
+    }
+
+    /// Checks that `account` can spend `amount`, leaving any flash loan untouched.
     fn check_balance(&self, account: &ActorId, amount: u128) -> Result<(), FTError> {
-        if *self.balances.get(account).unwrap_or(&0) < amount {
+        let balance = *self.balances.get(account).unwrap_or(&0);
+        if balance.saturating_sub(self.locked_balance(account)) < amount {
             return Err(FTError::NotEnoughBalance);
         }
         Ok(())
     }
 
+    fn set_limits(&mut self, limits: TransferLimits) -> Result<FTReply, FTError> {
+        if !self.admins.contains(&msg::source()) {
+            return Err(FTError::NotAdmin);
+        }
+        self.config.limits = limits.clone();
+        emit(FTEvent::LimitsSet {
+            limits: limits.clone(),
+        });
+        Ok(FTReply::LimitsSet { limits })
+    }
+
+    fn add_limit_exemption(&mut self, account: ActorId) -> Result<FTReply, FTError> {
+        if !self.admins.contains(&msg::source()) {
+            return Err(FTError::NotAdmin);
+        }
+        self.limit_exempt.insert(account);
+        emit(FTEvent::LimitExemptionAdded { account });
+        Ok(FTReply::LimitExemptionAdded { account })
+    }
+
+    fn remove_limit_exemption(&mut self, account: ActorId) -> Result<FTReply, FTError> {
+        if !self.admins.contains(&msg::source()) {
+            return Err(FTError::NotAdmin);
+        }
+        self.limit_exempt.remove(&account);
+        emit(FTEvent::LimitExemptionRemoved { account });
+        Ok(FTReply::LimitExemptionRemoved { account })
+    }
+
+    fn update_description(&mut self, description: String) -> Result<FTReply, FTError> {
+        self.check_metadata_admin()?;
+        validate_description(&description)?;
+        self.description = description.clone();
+        emit(FTEvent::DescriptionUpdated {
+            description: description.clone(),
+        });
+        Ok(FTReply::DescriptionUpdated { description })
+    }
+
+    fn update_external_link(&mut self, link: ExternalLink) -> Result<FTReply, FTError> {
+        self.check_metadata_admin()?;
+        let mut external_links = self.external_links.clone();
+        match link.clone() {
+            ExternalLink::Image(image) => external_links.image = image,
+            ExternalLink::Website(website) => external_links.website = website,
+            ExternalLink::Telegram(telegram) => external_links.telegram = telegram,
+            ExternalLink::Twitter(twitter) => external_links.twitter = twitter,
+            ExternalLink::Discord(discord) => external_links.discord = discord,
+            ExternalLink::Tokenomics(tokenomics) => external_links.tokenomics = tokenomics,
+        }
+        validate_external_links(&external_links)?;
+        self.external_links = external_links;
+        emit(FTEvent::ExternalLinkUpdated { link: link.clone() });
+        Ok(FTReply::ExternalLinkUpdated { link })
+    }
+
+    fn lock_metadata(&mut self) -> Result<FTReply, FTError> {
+        self.check_metadata_admin()?;
+        self.metadata_locked = true;
+        emit(FTEvent::MetadataLocked);
+        Ok(FTReply::MetadataLocked)
+    }
+
+    fn check_metadata_admin(&self) -> Result<(), FTError> {
+        if !self.admins.contains(&msg::source()) {
+            return Err(FTError::NotAdmin);
+        }
+        if self.metadata_locked {
+            return Err(FTError::MetadataLocked);
+        }
+        Ok(())
+    }
+
+    /// Checks a transfer of `amount` from `from` to `to` against `config.limits`.
+    fn check_limits(&self, from: &ActorId, to: &ActorId, amount: u128) -> Result<(), FTError> {
+        self.check_sender_limits(from, amount)?;
+        self.check_wallet_balance(to, amount)
+    }
+
+    /// Checks the transfer amount and cooldown limits of `from`.
+    fn check_sender_limits(&self, from: &ActorId, amount: u128) -> Result<(), FTError> {
+        if self.limit_exempt.contains(from) {
+            return Ok(());
+        }
+        let limits = &self.config.limits;
+        if limits.max_transfer_amount.is_some_and(|max| amount > max) {
+            return Err(FTError::MaxTransferAmountExceeded);
+        }
+        if let Some(last_block) = self.last_transfer_block.get(from) {
+            if exec::block_height() < last_block.saturating_add(limits.transfer_cooldown) {
+                return Err(FTError::TransferCooldown);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that crediting `amount` keeps `to` within `max_wallet_balance`.
+    fn check_wallet_balance(&self, to: &ActorId, amount: u128) -> Result<(), FTError> {
+        if self.limit_exempt.contains(to) {
+            return Ok(());
+        }
+        let balance = *self.balances.get(to).unwrap_or(&0);
+        if self
+            .config
+            .limits
+            .max_wallet_balance
+            .is_some_and(|max| balance.saturating_add(amount) > max)
+        {
+            return Err(FTError::MaxWalletBalanceExceeded);
+        }
+        Ok(())
+    }
+
+    /// Starts the transfer cooldown of `account`, forgetting the accounts
+    /// whose cooldown is over.
+    fn record_transfer(&mut self, account: &ActorId) {
+        let block_height = exec::block_height();
+        let cooldown = self.config.limits.transfer_cooldown;
+        self.last_transfer_block
+            .retain(|_, last_block| block_height < last_block.saturating_add(cooldown));
+        if cooldown > 0 && !self.limit_exempt.contains(account) {
+            self.last_transfer_block.insert(*account, block_height);
+        }
+    }
+
+    /// Serves a [`legacy::FTAction`], replying with a [`legacy::FTEvent`].
+    ///
+    /// The legacy token had no error type, so errors panic and the caller
+    /// receives an error reply, as it did before.
+    fn handle_legacy(&mut self, action: legacy::FTAction) {
+        let source = msg::source();
+        let event = match action {
+            legacy::FTAction::Mint(amount) => legacy_event(self.mint(amount, source)),
+            legacy::FTAction::Burn(amount) => legacy_event(self.burn(amount)),
+            legacy::FTAction::Transfer { from, to, amount } => {
+                legacy_event(self.transfer(None, &from, &to, amount))
+            }
+            legacy::FTAction::Approve { to, amount } => {
+                legacy_event(self.approve(None, &to, amount))
+            }
+            legacy::FTAction::TotalSupply => legacy::FTEvent::TotalSupply(self.current_supply),
+            legacy::FTAction::BalanceOf(account) => {
+                legacy::FTEvent::Balance(*self.balances.get(&account).unwrap_or(&0))
+            }
+        };
+        msg::reply(event, msg::value()).expect("Error in sending a reply");
+    }
+
+    /// Credits `amount` to `account`, skipping empty credits so that
+    /// `balances` only ever holds accounts with a positive balance.
+    fn increase_balance(&mut self, account: &ActorId, amount: u128) -> Result<(), FTError> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let balance = self.balances.entry(*account).or_default();
+        *balance = balance
+            .checked_add(amount)
+            .ok_or(FTError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Returns the part of the balance of `account` lent by a pending flash mint.
+    fn locked_balance(&self, account: &ActorId) -> u128 {
+        *self.flash_loans.get(account).unwrap_or(&0)
+    }
+
+    /// Debits `amount` from `account` and removes the entry once it drops to zero.
+    ///
+    /// The locked part of the balance cannot be debited.
+    fn decrease_balance(&mut self, account: &ActorId, amount: u128) -> Result<(), FTError> {
+        if amount == 0 {
+            return Ok(());
+        }
+        let locked = self.locked_balance(account);
+        let Entry::Occupied(mut balance) = self.balances.entry(*account) else {
+            return Err(FTError::NotEnoughBalance);
+        };
+        let remaining = balance
+            .get()
+            .checked_sub(amount)
+            .filter(|remaining| *remaining >= locked)
+            .ok_or(FTError::NotEnoughBalance)?;
+        if remaining == 0 {
+            balance.remove_entry();
+        } else {
+            *balance.get_mut() = remaining;
+        }
+        Ok(())
+    }
+
+    /// Returns a page of holders ordered by account id.
+    fn holders(&self, offset: u32, limit: u32) -> Vec<(ActorId, u128)> {
+        let mut holders: Vec<(ActorId, u128)> = self
+            .balances
+            .iter()
+            .map(|(account, balance)| (*account, *balance))
+            .collect();
+        holders.sort_unstable_by_key(|(account, _)| *account);
+        holders
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
     fn can_transfer(
         &mut self,
         source: &ActorId,
@@ -243,31 +739,50 @@ impl FungibleToken {
         Ok(())
     }
 
+    /// Records the reply of a transaction, successful or failed, so that retries
+    /// with the same `tx_id` get it back for `tx_storage_period` instead of
+    /// executing twice.
     fn set_tx_id_status(
         &mut self,
         tx_id: Option<TxId>,
         account: &ActorId,
-        valid_until: ValidUntil,
+        block_timestamp: u64,
+        reply: &Result<FTReply, FTError>,
     ) {
         if let Some(tx_id) = tx_id {
-            self.tx_ids.insert((*account, tx_id), valid_until);
+            let record = TxRecord {
+                valid_until: block_timestamp.saturating_add(self.config.tx_storage_period),
+                reply: reply.clone(),
+            };
+            self.tx_ids.insert((*account, tx_id), record);
+            self.account_to_tx_ids
+                .entry(*account)
+                .or_default()
+                .insert(tx_id);
         }
     }
 
-    fn check_tx_id(&self, tx_id: TxId, account: &ActorId) -> Result<(), FTError> {
-        if self.tx_ids.get(&(*account, tx_id)).is_some() {
-            return Err(FTError::TxAlreadyExists);
-        }
-
-        Ok(())
+    /// Drops outdated transactions of `account` and returns the original reply
+    /// if `tx_id` has already been executed.
+    fn executed_tx_reply(
+        &mut self,
+        tx_id: Option<TxId>,
+        account: &ActorId,
+        block_timestamp: u64,
+    ) -> Option<Result<FTReply, FTError>> {
+        let tx_id = tx_id?;
+        self.clear_outdated_tx_ids(account, block_timestamp);
+        self.tx_ids
+            .get(&(*account, tx_id))
+            .map(|record| record.reply.clone())
     }
 
     fn clear_outdated_tx_ids(&mut self, account: &ActorId, block_timestamp: u64) {
         if let Entry::Occupied(mut tx_ids) = self.account_to_tx_ids.entry(*account) {
             let tx_ids_cloned = tx_ids.get().clone();
             for tx_id in tx_ids_cloned {
-                let valid_until = self.tx_ids.get(&(*account, tx_id)).expect("Cant be None");
-                if block_timestamp > *valid_until {
+                let record = self.tx_ids.get(&(*account, tx_id)).expect("Cant be None");
+                if block_timestamp > record.valid_until {
                     self.tx_ids.remove(&(*account, tx_id));
                     tx_ids.get_mut().remove(&tx_id);
                 }
@@ -279,14 +794,38 @@ impl FungibleToken {
     }
 }
 
-#[no_mangle]
-extern "C" fn handle() {
-    let action: FTAction = msg::load().expect("Could not load Action");
+/// Publishes `event` for indexers by sending it to the zero address.
+fn emit(event: FTEvent) {
+    msg::send(ZERO_ID, event, 0).expect("Error in sending an event");
+}
+
+fn legacy_event(reply: Result<FTReply, FTError>) -> legacy::FTEvent {
+    match reply {
+        Ok(FTReply::Transferred { from, to, amount }) => {
+            legacy::FTEvent::Transfer { from, to, amount }
+        }
+        Ok(FTReply::Approved { from, to, amount }) => legacy::FTEvent::Approve { from, to, amount },
+        Ok(reply) => unreachable!("Unexpected reply to a legacy action: {reply:?}"),
+        Err(error) => panic!("{error:?}"),
+    }
+}
+
+#[gstd::async_main]
+async fn main() {
+    let payload = msg::load_bytes().expect("Could not load Action");
     let ft: &mut FungibleToken = unsafe {
         FUNGIBLE_TOKEN
             .as_mut()
             .expect("The contract is not initialized")
     };
+    let action = match FTAction::decode_all(&mut &payload[..]) {
+        Ok(action) => action,
+        Err(_) => {
+            let action =
+                legacy::FTAction::decode_all(&mut &payload[..]).expect("Could not load Action");
+            return ft.handle_legacy(action);
+        }
+    };
     let reply = match action {
         FTAction::TransferToUsers { amount, to_users } => ft.transfer_to_users(amount, to_users),
         FTAction::Mint { amount, to } => ft.mint(amount, to),
@@ -300,32 +839,117 @@ extern "C" fn handle() {
             amount,
         } => ft.transfer(tx_id, &from, &to, amount),
         FTAction::Approve { tx_id, to, amount } => ft.approve(tx_id, &to, amount),
+        // `BalanceOf` is encoded identically in both formats.
         FTAction::BalanceOf(account) => {
+            return ft.handle_legacy(legacy::FTAction::BalanceOf(account));
+        }
+        FTAction::GetBalance(account) => {
             let balance = ft.balances.get(&account).unwrap_or(&0);
             Ok(FTReply::Balance(*balance))
         }
+        FTAction::Deposit => ft.deposit(),
+        FTAction::Withdraw { amount } => ft.withdraw(amount),
+        FTAction::FlashMint {
+            receiver,
+            amount,
+            payload,
+        } => ft.flash_mint(receiver, amount, payload).await,
+        FTAction::SetLimits { limits } => ft.set_limits(limits),
+        FTAction::AddLimitExemption { account } => ft.add_limit_exemption(account),
+        FTAction::RemoveLimitExemption { account } => ft.remove_limit_exemption(account),
+        FTAction::AdminBurnFrom { from, amount } => ft.admin_burn_from(&from, amount),
+        FTAction::UpdateDescription { description } => ft.update_description(description),
+        FTAction::UpdateExternalLink { link } => ft.update_external_link(link),
+        FTAction::LockMetadata => ft.lock_metadata(),
     };
-    msg::reply(reply, 0).expect("Error in sending a reply");
+    // Only deposits keep the attached value; unwrapped value goes back with the reply.
+    let value = match &reply {
+        Ok(FTReply::Deposited { .. }) => 0,
+        Ok(FTReply::Withdrawn { amount, .. }) => amount.saturating_add(msg::value()),
+        _ => msg::value(),
+    };
+    msg::reply(reply, value).expect("Error in sending a reply");
 }
 
-#[no_mangle]
-extern "C" fn init() {
-    let init_config: InitConfig = msg::load().expect("Unable to decode InitConfig");
-
+fn validate_init_config(init_config: &InitConfig) -> Result<(), FTError> {
+    if init_config.admin == ZERO_ID {
+        return Err(FTError::ZeroAddress);
+    }
     if init_config.initial_supply > init_config.total_supply {
-        msg::reply(FTError::SupplyError, 0).expect("Error in sending a reply");
+        return Err(FTError::SupplyError);
+    }
+    if init_config.config.native_wrapper && init_config.initial_supply > 0 {
+        return Err(FTError::UnbackedSupply);
+    }
+    if init_config.decimals > MAX_DECIMALS {
+        return Err(FTError::DecimalsError);
+    }
+    if !(1..=MAX_NAME_LENGTH).contains(&init_config.name.chars().count()) {
+        return Err(FTError::NameError);
+    }
+    if !(1..=MAX_SYMBOL_LENGTH).contains(&init_config.symbol.chars().count()) {
+        return Err(FTError::SymbolError);
+    }
+    validate_description(&init_config.description)?;
+    validate_external_links(&init_config.external_links)?;
+    if init_config
+        .initial_capacity
+        .is_some_and(|capacity| capacity > MAX_INITIAL_CAPACITY)
+    {
+        return Err(FTError::CapacityError);
     }
+    Ok(())
+}
 
-    if init_config.description.chars().count() > 500 {
-        msg::reply(FTError::DescriptionError, 0).expect("Error in sending a reply");
+fn validate_description(description: &str) -> Result<(), FTError> {
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(FTError::DescriptionError);
     }
+    Ok(())
+}
 
-    if init_config.decimals > 100 {
-        msg::reply(FTError::DecimalsError, 0).expect("Error in sending a reply");
+/// Every link that is set must be a valid URL; an empty `image` counts as unset.
+fn validate_external_links(links: &ExternalLinks) -> Result<(), FTError> {
+    let optional_links = [
+        &links.website,
+        &links.telegram,
+        &links.twitter,
+        &links.discord,
+        &links.tokenomics,
+    ];
+    if (!links.image.is_empty() && !is_valid_url(&links.image))
+        || optional_links
+            .into_iter()
+            .flatten()
+            .any(|link| !is_valid_url(link))
+    {
+        return Err(FTError::ExternalLinksError);
     }
+    Ok(())
+}
+
+/// Accepts `http(s)://` and `ipfs://` URLs with a non-empty remainder and no whitespace.
+fn is_valid_url(url: &str) -> bool {
+    let rest = ["https://", "http://", "ipfs://"]
+        .into_iter()
+        .find_map(|scheme| url.strip_prefix(scheme));
+    url.len() <= MAX_URL_LENGTH
+        && rest.is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+}
 
-    let mut balances = HashMap::new();
-    balances.insert(init_config.admin, init_config.initial_supply);
+#[no_mangle]
+extern "C" fn init() {
+    let init_config: InitConfig = msg::load().expect("Unable to decode InitConfig");
+
+    if let Err(error) = validate_init_config(&init_config) {
+        panic!("Invalid InitConfig: {error:?}");
+    }
+
+    let capacity = init_config.initial_capacity.unwrap_or_default() as usize;
+    let mut balances = HashMap::with_capacity(capacity);
+    if init_config.initial_supply > 0 {
+        balances.insert(init_config.admin, init_config.initial_supply);
+    }
 
     let ft = FungibleToken {
         name: init_config.name,
@@ -336,6 +960,7 @@ extern "C" fn init() {
         current_supply: init_config.initial_supply,
         total_supply: init_config.total_supply,
         balances,
+        allowances: HashMap::with_capacity(capacity),
         admins: vec![init_config.admin],
         config: init_config.config,
         ..Default::default()
@@ -386,18 +1011,42 @@ extern "C" fn state() {
         }
         Query::Admins => QueryReply::Admins(token.admins.clone()),
         Query::GetTxValidityTime { account, tx_id } => {
-            let valid_until = token.tx_ids.get(&(account, tx_id)).unwrap_or(&0);
-            QueryReply::TxValidityTime(*valid_until)
+            let block_timestamp = exec::block_timestamp();
+            let valid_until = token
+                .tx_ids
+                .get(&(account, tx_id))
+                .filter(|record| record.valid_until >= block_timestamp)
+                .map_or(0, |record| record.valid_until);
+            QueryReply::TxValidityTime(valid_until)
         }
         Query::GetTxIdsForAccount { account } => {
-            let tx_ids: Vec<TxId> =
-                if let Some(tx_ids) = token.account_to_tx_ids.get(&account).cloned() {
-                    tx_ids.into_iter().collect()
-                } else {
-                    Vec::new()
-                };
+            // Outdated records are only cleared by the next transaction of the account.
+            let block_timestamp = exec::block_timestamp();
+            let tx_ids = token
+                .account_to_tx_ids
+                .get(&account)
+                .into_iter()
+                .flatten()
+                .filter(|tx_id| {
+                    token
+                        .tx_ids
+                        .get(&(account, **tx_id))
+                        .is_some_and(|record| record.valid_until >= block_timestamp)
+                })
+                .copied()
+                .collect();
             QueryReply::TxIdsForAccount { tx_ids }
         }
+        Query::GetTxRecord { account, tx_id } => {
+            QueryReply::TxRecord(token.tx_ids.get(&(account, tx_id)).cloned())
+        }
+        Query::Holders { offset, limit } => QueryReply::Holders(token.holders(offset, limit)),
+        Query::HolderCount => QueryReply::HolderCount(token.balances.len() as u32),
+        Query::Limits => QueryReply::Limits {
+            limits: token.config.limits.clone(),
+            exempt_accounts: token.limit_exempt.iter().copied().collect(),
+        },
+        Query::MetadataLocked => QueryReply::MetadataLocked(token.metadata_locked),
     };
     msg::reply(reply, 0).expect("Error on sharinf state");
 }
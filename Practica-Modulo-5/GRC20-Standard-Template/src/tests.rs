@@ -1 +1,1087 @@
+use fungible_token_io::*;
+use gstd::{prelude::*, ActorId};
+use gtest::{Log, Program, System, WasmProgram};
 
+mod invariants;
+
+const ADMIN: u64 = 100;
+const USER: u64 = 101;
+const OTHER: u64 = 102;
+const PROXY: u64 = 200;
+const VALUE: u128 = 10_000_000_000_000;
+
+fn init_config(config: Config) -> InitConfig {
+    InitConfig {
+        name: String::from("Wrapped VARA"),
+        symbol: String::from("WVARA"),
+        decimals: 12,
+        description: String::new(),
+        external_links: ExternalLinks {
+            image: String::from("https://vara.network/logo.png"),
+            ..Default::default()
+        },
+        initial_supply: 0,
+        total_supply: 1_000,
+        admin: ADMIN.into(),
+        initial_capacity: None,
+        config,
+    }
+}
+
+fn init_token(system: &System, config: Config) -> Program<'_> {
+    let program = Program::current(system);
+    let result = program.send(ADMIN, init_config(config));
+    assert!(!result.main_failed());
+    program
+}
+
+fn wrapper_config() -> Config {
+    Config {
+        native_wrapper: true,
+        ..Default::default()
+    }
+}
+
+fn balance_of(program: &Program<'_>, account: impl Into<ActorId>) -> u128 {
+    match program
+        .read_state(Query::BalanceOf(account.into()))
+        .expect("Unable to read state")
+    {
+        QueryReply::Balance(balance) => balance,
+        _ => unreachable!(),
+    }
+}
+
+fn current_supply(program: &Program<'_>) -> u128 {
+    match program
+        .read_state(Query::CurrentSupply)
+        .expect("Unable to read state")
+    {
+        QueryReply::CurrentSupply(supply) => supply,
+        _ => unreachable!(),
+    }
+}
+
+fn total_supply(program: &Program<'_>) -> u128 {
+    match program
+        .read_state(Query::TotalSupply)
+        .expect("Unable to read state")
+    {
+        QueryReply::TotalSupply(supply) => supply,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn deposit_and_withdraw() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, 2 * VALUE);
+
+    let program = init_token(&system, wrapper_config());
+
+    let result = program.send_with_value(USER, FTAction::Deposit, VALUE);
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<FTReply, FTError>(
+            FTReply::Deposited {
+                to: ActorId::from(USER),
+                amount: VALUE,
+            }
+        )))
+    );
+    assert_eq!(balance_of(&program, USER), VALUE);
+    assert_eq!(system.balance_of(program.id()), VALUE);
+    // The mint cap is untouched by wrapped value.
+    assert_eq!(total_supply(&program), 1_000 + VALUE);
+
+    let result = program.send(USER, FTAction::Withdraw { amount: VALUE / 2 });
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<FTReply, FTError>(
+            FTReply::Withdrawn {
+                from: ActorId::from(USER),
+                amount: VALUE / 2,
+            }
+        )))
+    );
+    assert_eq!(balance_of(&program, USER), VALUE / 2);
+    assert_eq!(system.balance_of(program.id()), VALUE / 2);
+    assert_eq!(total_supply(&program), 1_000 + VALUE / 2);
+}
+
+#[test]
+fn withdraw_more_than_balance() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, VALUE);
+
+    let program = init_token(&system, wrapper_config());
+    program.send_with_value(USER, FTAction::Deposit, VALUE);
+
+    let result = program.send(USER, FTAction::Withdraw { amount: VALUE + 1 });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::NotEnoughBalance))
+    ));
+    assert_eq!(balance_of(&program, USER), VALUE);
+    assert_eq!(system.balance_of(program.id()), VALUE);
+}
+
+#[test]
+fn wrapped_supply_is_backed_by_program_balance() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, VALUE);
+    system.mint_to(OTHER, VALUE);
+
+    let program = Program::current(&system);
+    let result = program.send(
+        ADMIN,
+        InitConfig {
+            initial_supply: 1,
+            ..init_config(wrapper_config())
+        },
+    );
+    assert!(result.main_failed());
+
+    let program = init_token(&system, wrapper_config());
+    let error = |error| {
+        Log::builder()
+            .dest(ADMIN)
+            .payload(Err::<FTReply, FTError>(error))
+    };
+
+    program.send_with_value(USER, FTAction::Deposit, VALUE);
+    program.send_with_value(OTHER, FTAction::Deposit, VALUE / 4);
+    assert_eq!(current_supply(&program), system.balance_of(program.id()));
+
+    let result = program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 1,
+            to: ADMIN.into(),
+        },
+    );
+    assert!(result.contains(&error(FTError::UnbackedSupply)));
+    let result = program.send(
+        ADMIN,
+        FTAction::FlashMint {
+            receiver: USER.into(),
+            amount: 1,
+            payload: vec![],
+        },
+    );
+    assert!(result.contains(&error(FTError::UnbackedSupply)));
+    assert_eq!(current_supply(&program), system.balance_of(program.id()));
+
+    let result = program.send(OTHER, FTAction::Burn { amount: 1 });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(OTHER)
+            .payload(Err::<FTReply, FTError>(FTError::WrappedBurn))
+    ));
+    let result = program.send(
+        ADMIN,
+        FTAction::AdminBurnFrom {
+            from: OTHER.into(),
+            amount: 1,
+        },
+    );
+    assert!(result.contains(&error(FTError::WrappedBurn)));
+
+    // Value attached to a successful action other than `Deposit` is refunded.
+    let result = program.send_with_value(
+        OTHER,
+        FTAction::Approve {
+            tx_id: None,
+            to: USER.into(),
+            amount: 1,
+        },
+        VALUE / 4,
+    );
+    assert!(!result.main_failed());
+    assert_eq!(current_supply(&program), system.balance_of(program.id()));
+
+    program.send(USER, FTAction::Withdraw { amount: VALUE / 3 });
+    assert_eq!(current_supply(&program), VALUE - VALUE / 3 + VALUE / 4);
+    assert_eq!(current_supply(&program), system.balance_of(program.id()));
+}
+
+/// Flash mint receiver that replies to the callback with arbitrary bytes
+/// only if `replies` is set.
+#[derive(Debug)]
+struct FlashMintReceiver {
+    replies: bool,
+}
+
+impl WasmProgram for FlashMintReceiver {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(self.replies.then(Vec::new))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+fn flash_mint_receiver(system: &System, replies: bool) -> (Program<'_>, ActorId) {
+    let receiver = Program::mock(system, FlashMintReceiver { replies });
+    receiver.send_bytes(ADMIN, b"");
+    let id = receiver.id().into_bytes().into();
+    (receiver, id)
+}
+
+fn init_proxy<'a>(system: &'a System, token: &Program<'_>) -> (Program<'a>, ActorId) {
+    let proxy = Program::from_binary_with_id(system, PROXY, fungible_token_proxy::WASM_BINARY);
+    let result = proxy.send(ADMIN, ActorId::from(token.id().into_bytes()));
+    assert!(!result.main_failed());
+    (proxy, PROXY.into())
+}
+
+fn flash_mint_config() -> Config {
+    Config {
+        flash_mint_fee_bps: 100,
+        flash_mint_fee_collector: ADMIN.into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn repaid_flash_mint_pays_the_fee() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, flash_mint_config());
+    let (proxy, receiver_id) = init_proxy(&system, &program);
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 5,
+            to: receiver_id,
+        },
+    );
+    // The receiver lets the token take the repayment.
+    let result = proxy.send(
+        ADMIN,
+        FTAction::Approve {
+            tx_id: None,
+            to: program.id().into_bytes().into(),
+            amount: 505,
+        },
+    );
+    assert!(!result.main_failed());
+
+    let result = program.send(
+        USER,
+        FTAction::FlashMint {
+            receiver: receiver_id,
+            amount: 500,
+            payload: vec![],
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<FTReply, FTError>(
+            FTReply::FlashMinted {
+                receiver: receiver_id,
+                amount: 500,
+                fee: 5,
+            }
+        )))
+    );
+    assert_eq!(balance_of(&program, ADMIN), 5);
+    assert_eq!(balance_of(&program, receiver_id), 0);
+    assert_eq!(current_supply(&program), 5);
+}
+
+#[test]
+fn unpaid_flash_mint_is_burnt() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, flash_mint_config());
+    let (_receiver, receiver_id) = flash_mint_receiver(&system, true);
+    let flash_mint = |amount| FTAction::FlashMint {
+        receiver: receiver_id,
+        amount,
+        payload: vec![],
+    };
+    let error = |error| {
+        Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(error))
+    };
+
+    // Flash mints stay within the supply cap.
+    let result = program.send(USER, flash_mint(1_001));
+    assert!(result.contains(&error(FTError::MaxSupplyReached)));
+
+    // A program replying with arbitrary bytes has not consented to pay the
+    // fee, even though it holds enough tokens.
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 5,
+            to: receiver_id,
+        },
+    );
+    let result = program.send(USER, flash_mint(500));
+    assert!(result.contains(&error(FTError::FlashMintNotRepaid)));
+    assert_eq!(balance_of(&program, receiver_id), 5);
+    assert_eq!(current_supply(&program), 5);
+
+    // Accepting without an allowance does not repay the loan either.
+    let (_proxy, proxy_id) = init_proxy(&system, &program);
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 5,
+            to: proxy_id,
+        },
+    );
+    let result = program.send(
+        USER,
+        FTAction::FlashMint {
+            receiver: proxy_id,
+            amount: 500,
+            payload: vec![],
+        },
+    );
+    assert!(result.contains(&error(FTError::FlashMintNotRepaid)));
+    assert_eq!(balance_of(&program, proxy_id), 5);
+    assert_eq!(current_supply(&program), 10);
+
+    // User accounts cannot reply to the callback.
+    let result = program.send(
+        USER,
+        FTAction::FlashMint {
+            receiver: USER.into(),
+            amount: 500,
+            payload: vec![],
+        },
+    );
+    assert!(result.contains(&error(FTError::InvalidFlashMintReceiver)));
+}
+
+#[test]
+fn timed_out_flash_mint_is_burnt() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, flash_mint_config());
+    let (_receiver, receiver_id) = flash_mint_receiver(&system, false);
+    let flash_mint = || FTAction::FlashMint {
+        receiver: receiver_id,
+        amount: 500,
+        payload: vec![],
+    };
+
+    program.send(USER, flash_mint());
+    assert_eq!(balance_of(&program, receiver_id), 500);
+    let result = program.send(USER, flash_mint());
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::FlashMintInProgress))
+    ));
+
+    system.spend_blocks(crate::FLASH_MINT_TIMEOUT);
+    assert_eq!(balance_of(&program, receiver_id), 0);
+    assert_eq!(current_supply(&program), 0);
+}
+
+#[test]
+fn deposit_requires_wrapper_config() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, VALUE);
+
+    let program = init_token(&system, Config::default());
+
+    let result = program.send_with_value(USER, FTAction::Deposit, VALUE);
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::NotNativeWrapper))
+    ));
+    assert_eq!(balance_of(&program, USER), 0);
+    // The attached value is refunded with the error reply.
+    assert_eq!(system.balance_of(program.id()), 0);
+
+    let result = program.send(USER, FTAction::Withdraw { amount: 1 });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::NotNativeWrapper))
+    ));
+}
+
+#[test]
+fn duplicate_tx_id_returns_original_reply() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 100,
+            to: ADMIN.into(),
+        },
+    );
+
+    let transfer = || FTAction::Transfer {
+        tx_id: Some(1),
+        from: ADMIN.into(),
+        to: USER.into(),
+        amount: 40,
+    };
+    let reply = Ok::<FTReply, FTError>(FTReply::Transferred {
+        from: ADMIN.into(),
+        to: USER.into(),
+        amount: 40,
+    });
+
+    let result = program.send(ADMIN, transfer());
+    assert!(result.contains(&Log::builder().dest(ADMIN).payload(reply.clone())));
+    let result = program.send(ADMIN, transfer());
+    assert!(result.contains(&Log::builder().dest(ADMIN).payload(reply)));
+    assert_eq!(balance_of(&program, USER), 40);
+
+    match program
+        .read_state(Query::GetTxIdsForAccount {
+            account: ADMIN.into(),
+        })
+        .expect("Unable to read state")
+    {
+        QueryReply::TxIdsForAccount { tx_ids } => assert_eq!(tx_ids, vec![1]),
+        _ => unreachable!(),
+    }
+    match program
+        .read_state(Query::GetTxRecord {
+            account: ADMIN.into(),
+            tx_id: 1,
+        })
+        .expect("Unable to read state")
+    {
+        QueryReply::TxRecord(Some(TxRecord {
+            reply: Ok(FTReply::Transferred { amount, .. }),
+            ..
+        })) => assert_eq!(amount, 40),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn failed_tx_ids_are_recorded_until_outdated() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(
+        &system,
+        Config {
+            tx_storage_period: 60_000,
+            ..Default::default()
+        },
+    );
+    let transfer = || FTAction::Transfer {
+        tx_id: Some(1),
+        from: USER.into(),
+        to: ADMIN.into(),
+        amount: 10,
+    };
+    let error = Log::builder()
+        .dest(USER)
+        .payload(Err::<FTReply, FTError>(FTError::NotEnoughBalance));
+    let tx_ids = || match program
+        .read_state(Query::GetTxIdsForAccount {
+            account: USER.into(),
+        })
+        .expect("Unable to read state")
+    {
+        QueryReply::TxIdsForAccount { tx_ids } => tx_ids,
+        _ => unreachable!(),
+    };
+    let valid_until = || match program
+        .read_state(Query::GetTxValidityTime {
+            account: USER.into(),
+            tx_id: 1,
+        })
+        .expect("Unable to read state")
+    {
+        QueryReply::TxValidityTime(valid_until) => valid_until,
+        _ => unreachable!(),
+    };
+
+    let result = program.send(USER, transfer());
+    assert!(result.contains(&error));
+    assert_eq!(tx_ids(), vec![1]);
+    assert_ne!(valid_until(), 0);
+
+    // The failure is replayed even though the transfer would now succeed.
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 10,
+            to: USER.into(),
+        },
+    );
+    let result = program.send(USER, transfer());
+    assert!(result.contains(&error));
+    assert_eq!(balance_of(&program, USER), 10);
+
+    // Well past the storage period of a minute.
+    system.spend_blocks(100);
+    assert!(tx_ids().is_empty());
+    assert_eq!(valid_until(), 0);
+    let result = program.send(USER, transfer());
+    assert!(!result.contains(&error));
+    assert_eq!(balance_of(&program, USER), 0);
+}
+
+#[test]
+fn legacy_actions_get_legacy_replies() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+
+    let result = program.send(ADMIN, legacy::FTAction::Mint(50));
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(legacy::FTEvent::Transfer {
+                from: ActorId::zero(),
+                to: ADMIN.into(),
+                amount: 50,
+            })
+    ));
+
+    let result = program.send(
+        ADMIN,
+        legacy::FTAction::Transfer {
+            from: ADMIN.into(),
+            to: USER.into(),
+            amount: 20,
+        },
+    );
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(legacy::FTEvent::Transfer {
+                from: ADMIN.into(),
+                to: USER.into(),
+                amount: 20,
+            })
+    ));
+    assert_eq!(balance_of(&program, USER), 20);
+
+    let result = program.send(ADMIN, legacy::FTAction::TotalSupply);
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(legacy::FTEvent::TotalSupply(50))
+    ));
+
+    // Legacy errors surface as a failed message.
+    let result = program.send(USER, legacy::FTAction::Burn(21));
+    assert!(result.main_failed());
+}
+
+#[test]
+fn balance_of_gets_the_legacy_reply_from_any_sender() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 50,
+            to: USER.into(),
+        },
+    );
+    let (proxy, _) = init_proxy(&system, &program);
+
+    let legacy_balance = Log::builder()
+        .dest(ADMIN)
+        .payload(legacy::FTEvent::Balance(50));
+    let result = proxy.send(ADMIN, FTAction::BalanceOf(USER.into()));
+    assert!(result.contains(&legacy_balance));
+    let result = program.send(ADMIN, FTAction::BalanceOf(USER.into()));
+    assert!(result.contains(&legacy_balance));
+
+    let balance = Log::builder()
+        .dest(ADMIN)
+        .payload(Ok::<FTReply, FTError>(FTReply::Balance(50)));
+    let result = proxy.send(ADMIN, FTAction::GetBalance(USER.into()));
+    assert!(result.contains(&balance));
+    let result = program.send(ADMIN, FTAction::GetBalance(USER.into()));
+    assert!(result.contains(&balance));
+}
+
+#[test]
+fn transfer_limits() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(
+        &system,
+        Config {
+            limits: TransferLimits {
+                max_transfer_amount: Some(30),
+                max_wallet_balance: Some(50),
+                transfer_cooldown: 2,
+            },
+            ..Default::default()
+        },
+    );
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 100,
+            to: ADMIN.into(),
+        },
+    );
+    let transfer = |amount| FTAction::Transfer {
+        tx_id: None,
+        from: ADMIN.into(),
+        to: USER.into(),
+        amount,
+    };
+    let error = |error| {
+        Log::builder()
+            .dest(ADMIN)
+            .payload(Err::<FTReply, FTError>(error))
+    };
+
+    let result = program.send(ADMIN, transfer(31));
+    assert!(result.contains(&error(FTError::MaxTransferAmountExceeded)));
+
+    let result = program.send(ADMIN, transfer(30));
+    assert!(!result.main_failed());
+    let result = program.send(ADMIN, transfer(10));
+    assert!(result.contains(&error(FTError::TransferCooldown)));
+
+    system.spend_blocks(2);
+    let result = program.send(ADMIN, transfer(30));
+    assert!(result.contains(&error(FTError::MaxWalletBalanceExceeded)));
+
+    program.send(
+        ADMIN,
+        FTAction::AddLimitExemption {
+            account: USER.into(),
+        },
+    );
+    let result = program.send(ADMIN, transfer(30));
+    assert!(!result.main_failed());
+    assert_eq!(balance_of(&program, USER), 60);
+}
+
+#[test]
+fn wallet_cap_applies_to_every_credit() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, VALUE);
+
+    let limits = |max_wallet_balance| TransferLimits {
+        max_wallet_balance: Some(max_wallet_balance),
+        ..Default::default()
+    };
+    let error = |dest, error| {
+        Log::builder()
+            .dest(dest)
+            .payload(Err::<FTReply, FTError>(error))
+    };
+
+    let program = init_token(
+        &system,
+        Config {
+            limits: limits(50),
+            ..Default::default()
+        },
+    );
+    let result = program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 51,
+            to: USER.into(),
+        },
+    );
+    assert!(result.contains(&error(ADMIN, FTError::MaxWalletBalanceExceeded)));
+
+    program.send(
+        ADMIN,
+        FTAction::AddLimitExemption {
+            account: ADMIN.into(),
+        },
+    );
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 100,
+            to: ADMIN.into(),
+        },
+    );
+    let result = program.send(
+        ADMIN,
+        FTAction::TransferToUsers {
+            amount: 30,
+            to_users: vec![USER.into(), USER.into()],
+        },
+    );
+    assert!(result.contains(&error(ADMIN, FTError::MaxWalletBalanceExceeded)));
+    assert_eq!(balance_of(&program, USER), 0);
+    let result = program.send(
+        ADMIN,
+        FTAction::TransferToUsers {
+            amount: 30,
+            to_users: vec![USER.into(), OTHER.into()],
+        },
+    );
+    assert!(!result.main_failed());
+    assert_eq!(balance_of(&program, USER), 30);
+
+    let program = init_token(
+        &system,
+        Config {
+            limits: limits(VALUE / 2),
+            ..wrapper_config()
+        },
+    );
+    let result = program.send_with_value(USER, FTAction::Deposit, VALUE);
+    assert!(result.contains(&error(USER, FTError::MaxWalletBalanceExceeded)));
+    assert_eq!(system.balance_of(program.id()), 0);
+}
+
+#[test]
+fn state_changes_are_published_as_events() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+
+    let result = program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 10,
+            to: USER.into(),
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(0).payload(FTEvent::Transferred {
+            from: ActorId::zero(),
+            to: USER.into(),
+            amount: 10,
+        }))
+    );
+
+    let result = program.send(
+        USER,
+        FTAction::Approve {
+            tx_id: None,
+            to: ADMIN.into(),
+            amount: 5,
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(0).payload(FTEvent::Approved {
+            from: USER.into(),
+            to: ADMIN.into(),
+            amount: 5,
+        }))
+    );
+
+    let result = program.send(
+        ADMIN,
+        FTAction::AddAdmin {
+            admin_id: USER.into(),
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(0).payload(FTEvent::AdminAdded {
+            admin_id: USER.into(),
+        }))
+    );
+}
+
+#[test]
+fn supply_policies() {
+    let system = System::new();
+    system.init_logger();
+
+    for (policy, total_after_burn) in [
+        (SupplyPolicy::FixedCap, 1_000),
+        (SupplyPolicy::BurnReducesCap, 900),
+        (SupplyPolicy::Uncapped, 1_000),
+    ] {
+        let program = init_token(
+            &system,
+            Config {
+                supply_policy: policy,
+                ..Default::default()
+            },
+        );
+        program.send(
+            ADMIN,
+            FTAction::Mint {
+                amount: 1_000,
+                to: ADMIN.into(),
+            },
+        );
+        program.send(ADMIN, FTAction::Burn { amount: 100 });
+        assert_eq!(total_supply(&program), total_after_burn);
+
+        let result = program.send(
+            ADMIN,
+            FTAction::Mint {
+                amount: 200,
+                to: ADMIN.into(),
+            },
+        );
+        let minted = result.contains(&Log::builder().dest(ADMIN).payload(Ok::<FTReply, FTError>(
+            FTReply::Transferred {
+                from: ActorId::zero(),
+                to: ADMIN.into(),
+                amount: 200,
+            },
+        )));
+        assert_eq!(minted, policy == SupplyPolicy::Uncapped);
+    }
+}
+
+#[test]
+fn admin_burn_from_uses_allowance() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+    program.send(
+        ADMIN,
+        FTAction::Mint {
+            amount: 100,
+            to: USER.into(),
+        },
+    );
+    let burn_from = || FTAction::AdminBurnFrom {
+        from: USER.into(),
+        amount: 60,
+    };
+
+    let result = program.send(ADMIN, burn_from());
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(Err::<FTReply, FTError>(FTError::NotAllowedToTransfer))
+    ));
+
+    program.send(
+        USER,
+        FTAction::Approve {
+            tx_id: None,
+            to: ADMIN.into(),
+            amount: 60,
+        },
+    );
+    let result = program.send(ADMIN, burn_from());
+    assert!(!result.main_failed());
+    assert_eq!(balance_of(&program, USER), 40);
+    assert_eq!(total_supply(&program), 1_000);
+
+    let result = program.send(USER, burn_from());
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::NotAdmin))
+    ));
+}
+
+#[test]
+fn invalid_init_config_aborts_deployment() {
+    let system = System::new();
+    system.init_logger();
+
+    let invalid_configs = [
+        InitConfig {
+            initial_supply: 1_001,
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            decimals: 39,
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            symbol: String::new(),
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            description: "a".repeat(501),
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            external_links: ExternalLinks {
+                image: String::from("https://vara.network/logo.png"),
+                website: Some(String::from("vara network")),
+                ..Default::default()
+            },
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            initial_capacity: Some(10_001),
+            ..init_config(Config::default())
+        },
+        InitConfig {
+            external_links: ExternalLinks {
+                image: String::from("logo.png"),
+                ..Default::default()
+            },
+            ..init_config(Config::default())
+        },
+    ];
+    for config in invalid_configs {
+        let program = Program::current(&system);
+        let result = program.send(ADMIN, config);
+        assert!(result.main_failed());
+    }
+}
+
+#[test]
+fn external_links_may_be_left_empty() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = Program::current(&system);
+    let result = program.send(
+        ADMIN,
+        InitConfig {
+            external_links: ExternalLinks::default(),
+            ..init_config(Config::default())
+        },
+    );
+    assert!(!result.main_failed());
+}
+
+#[test]
+fn metadata_updates_until_locked() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+    let website = || ExternalLink::Website(Some(String::from("https://vara.network")));
+
+    let result = program.send(USER, FTAction::UpdateExternalLink { link: website() });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(USER)
+            .payload(Err::<FTReply, FTError>(FTError::NotAdmin))
+    ));
+
+    let result = program.send(
+        ADMIN,
+        FTAction::UpdateExternalLink {
+            link: ExternalLink::Image(String::from("not a url")),
+        },
+    );
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(Err::<FTReply, FTError>(FTError::ExternalLinksError))
+    ));
+
+    let result = program.send(ADMIN, FTAction::UpdateExternalLink { link: website() });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(0)
+            .payload(FTEvent::ExternalLinkUpdated { link: website() })
+    ));
+    match program
+        .read_state(Query::ExternalLinks)
+        .expect("Unable to read state")
+    {
+        QueryReply::ExternalLinks(links) => {
+            assert_eq!(links.website.as_deref(), Some("https://vara.network"))
+        }
+        _ => unreachable!(),
+    }
+
+    program.send(ADMIN, FTAction::LockMetadata);
+    let result = program.send(
+        ADMIN,
+        FTAction::UpdateDescription {
+            description: String::from("New description"),
+        },
+    );
+    assert!(result.contains(
+        &Log::builder()
+            .dest(ADMIN)
+            .payload(Err::<FTReply, FTError>(FTError::MetadataLocked))
+    ));
+}
+
+#[test]
+fn holders_pagination_and_count() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_token(&system, Config::default());
+    for (to, amount) in [(ADMIN, 50), (USER, 30), (OTHER, 20)] {
+        program.send(
+            ADMIN,
+            FTAction::Mint {
+                amount,
+                to: to.into(),
+            },
+        );
+    }
+    let holders = |offset, limit| match program
+        .read_state(Query::Holders { offset, limit })
+        .expect("Unable to read state")
+    {
+        QueryReply::Holders(holders) => holders,
+        _ => unreachable!(),
+    };
+    let holder_count = || match program
+        .read_state(Query::HolderCount)
+        .expect("Unable to read state")
+    {
+        QueryReply::HolderCount(count) => count,
+        _ => unreachable!(),
+    };
+
+    assert_eq!(holder_count(), 3);
+    assert_eq!(
+        holders(0, 2),
+        vec![(ActorId::from(ADMIN), 50), (ActorId::from(USER), 30)]
+    );
+    assert_eq!(holders(2, 2), vec![(ActorId::from(OTHER), 20)]);
+    assert!(holders(3, 2).is_empty());
+    assert!(holders(0, 0).is_empty());
+    assert_eq!(holders(1, u32::MAX).len(), 2);
+
+    // An account whose balance drops to zero is no longer a holder.
+    program.send(
+        USER,
+        FTAction::Transfer {
+            tx_id: None,
+            from: USER.into(),
+            to: ADMIN.into(),
+            amount: 30,
+        },
+    );
+    assert_eq!(holder_count(), 2);
+    assert_eq!(
+        holders(0, u32::MAX),
+        vec![(ActorId::from(ADMIN), 80), (ActorId::from(OTHER), 20)]
+    );
+}
@@ -0,0 +1,297 @@
+//! Random sequences of token actions checked against a reference model.
+//!
+//! After every step the contract state must match the model and satisfy:
+//! * the sum of balances equals `current_supply`;
+//! * `current_supply <= total_supply`;
+//! * allowances only move by the amounts that were approved or spent;
+//! * every reply matches the state change it caused.
+
+use super::*;
+use gstd::collections::HashMap;
+use proptest::prelude::*;
+
+const ACTORS: [u64; 4] = [ADMIN, USER, OTHER, 103];
+
+#[derive(Debug, Clone)]
+enum Op {
+    Mint {
+        actor: u64,
+        to: u64,
+        amount: u128,
+    },
+    Burn {
+        actor: u64,
+        amount: u128,
+    },
+    Transfer {
+        actor: u64,
+        from: u64,
+        to: u64,
+        amount: u128,
+    },
+    Approve {
+        actor: u64,
+        to: u64,
+        amount: u128,
+    },
+    TransferToUsers {
+        actor: u64,
+        to_users: Vec<u64>,
+        amount: u128,
+    },
+}
+
+/// Expected outcome of an [`Op`].
+enum Expected {
+    Reply(Result<FTReply, FTError>),
+    Panic,
+}
+
+#[derive(Default)]
+struct Model {
+    balances: HashMap<u64, u128>,
+    allowances: HashMap<(u64, u64), u128>,
+    current_supply: u128,
+    total_supply: u128,
+}
+
+impl Model {
+    fn balance(&self, account: u64) -> u128 {
+        *self.balances.get(&account).unwrap_or(&0)
+    }
+
+    fn credit(&mut self, account: u64, amount: u128) {
+        *self.balances.entry(account).or_default() += amount;
+    }
+
+    fn debit(&mut self, account: u64, amount: u128) {
+        *self.balances.entry(account).or_default() -= amount;
+    }
+
+    fn apply(&mut self, op: &Op) -> Expected {
+        match *op {
+            Op::Mint { actor, to, amount } => {
+                if actor != ADMIN {
+                    return Expected::Panic;
+                }
+                if self.current_supply + amount > self.total_supply {
+                    return Expected::Reply(Err(FTError::MaxSupplyReached));
+                }
+                self.current_supply += amount;
+                self.credit(to, amount);
+                Expected::Reply(Ok(FTReply::Transferred {
+                    from: ActorId::zero(),
+                    to: to.into(),
+                    amount,
+                }))
+            }
+            Op::Burn { actor, amount } => {
+                if self.balance(actor) < amount {
+                    return Expected::Reply(Err(FTError::NotEnoughBalance));
+                }
+                self.current_supply -= amount;
+                self.debit(actor, amount);
+                Expected::Reply(Ok(FTReply::Transferred {
+                    from: actor.into(),
+                    to: ActorId::zero(),
+                    amount,
+                }))
+            }
+            Op::Transfer {
+                actor,
+                from,
+                to,
+                amount,
+            } => {
+                if self.balance(from) < amount {
+                    return Expected::Reply(Err(FTError::NotEnoughBalance));
+                }
+                if from != actor {
+                    match self.allowances.get_mut(&(from, actor)) {
+                        Some(allowance) if *allowance >= amount => *allowance -= amount,
+                        _ => return Expected::Reply(Err(FTError::NotAllowedToTransfer)),
+                    }
+                }
+                self.debit(from, amount);
+                self.credit(to, amount);
+                Expected::Reply(Ok(FTReply::Transferred {
+                    from: from.into(),
+                    to: to.into(),
+                    amount,
+                }))
+            }
+            Op::Approve { actor, to, amount } => {
+                self.allowances.insert((actor, to), amount);
+                Expected::Reply(Ok(FTReply::Approved {
+                    from: actor.into(),
+                    to: to.into(),
+                    amount,
+                }))
+            }
+            Op::TransferToUsers {
+                actor,
+                ref to_users,
+                amount,
+            } => {
+                if actor != ADMIN {
+                    return Expected::Panic;
+                }
+                if self.balance(actor) < amount * to_users.len() as u128 {
+                    return Expected::Reply(Err(FTError::NotEnoughBalance));
+                }
+                for to in to_users {
+                    self.debit(actor, amount);
+                    self.credit(*to, amount);
+                }
+                Expected::Reply(Ok(FTReply::TransferredToUsers {
+                    from: actor.into(),
+                    to_users: to_users.iter().map(|to| (*to).into()).collect(),
+                    amount,
+                }))
+            }
+        }
+    }
+}
+
+impl Op {
+    fn actor(&self) -> u64 {
+        match *self {
+            Op::Mint { actor, .. }
+            | Op::Burn { actor, .. }
+            | Op::Transfer { actor, .. }
+            | Op::Approve { actor, .. }
+            | Op::TransferToUsers { actor, .. } => actor,
+        }
+    }
+
+    fn action(&self) -> FTAction {
+        match self.clone() {
+            Op::Mint { to, amount, .. } => FTAction::Mint {
+                amount,
+                to: to.into(),
+            },
+            Op::Burn { amount, .. } => FTAction::Burn { amount },
+            Op::Transfer {
+                from, to, amount, ..
+            } => FTAction::Transfer {
+                tx_id: None,
+                from: from.into(),
+                to: to.into(),
+                amount,
+            },
+            Op::Approve { to, amount, .. } => FTAction::Approve {
+                tx_id: None,
+                to: to.into(),
+                amount,
+            },
+            Op::TransferToUsers {
+                to_users, amount, ..
+            } => FTAction::TransferToUsers {
+                amount,
+                to_users: to_users.into_iter().map(Into::into).collect(),
+            },
+        }
+    }
+}
+
+fn actor() -> impl Strategy<Value = u64> {
+    prop::sample::select(ACTORS.to_vec())
+}
+
+fn amount() -> impl Strategy<Value = u128> {
+    0..600u128
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (actor(), actor(), amount()).prop_map(|(actor, to, amount)| Op::Mint { actor, to, amount }),
+        (actor(), amount()).prop_map(|(actor, amount)| Op::Burn { actor, amount }),
+        (actor(), actor(), actor(), amount()).prop_map(|(actor, from, to, amount)| {
+            Op::Transfer {
+                actor,
+                from,
+                to,
+                amount,
+            }
+        }),
+        (actor(), actor(), amount()).prop_map(|(actor, to, amount)| Op::Approve {
+            actor,
+            to,
+            amount
+        }),
+        (actor(), prop::collection::vec(actor(), 0..4), 0..200u128).prop_map(
+            |(actor, to_users, amount)| Op::TransferToUsers {
+                actor,
+                to_users,
+                amount,
+            }
+        ),
+    ]
+}
+
+fn check_state(program: &Program<'_>, model: &Model) -> Result<(), TestCaseError> {
+    let holders = match program.read_state(Query::Holders {
+        offset: 0,
+        limit: u32::MAX,
+    }) {
+        Ok(QueryReply::Holders(holders)) => holders,
+        _ => unreachable!(),
+    };
+    let current_supply = match program.read_state(Query::CurrentSupply) {
+        Ok(QueryReply::CurrentSupply(supply)) => supply,
+        _ => unreachable!(),
+    };
+    let total_supply = total_supply(program);
+
+    let sum: u128 = holders.iter().map(|(_, balance)| balance).sum();
+    prop_assert_eq!(sum, current_supply);
+    prop_assert!(current_supply <= total_supply);
+    prop_assert_eq!(current_supply, model.current_supply);
+    prop_assert_eq!(total_supply, model.total_supply);
+
+    for account in ACTORS {
+        prop_assert_eq!(balance_of(program, account), model.balance(account));
+        for approved in ACTORS {
+            let allowance = match program.read_state(Query::AllowanceOfAccount {
+                account: account.into(),
+                approved_account: approved.into(),
+            }) {
+                Ok(QueryReply::AllowanceOfAccount(allowance)) => allowance,
+                _ => unreachable!(),
+            };
+            let expected = *model.allowances.get(&(account, approved)).unwrap_or(&0);
+            prop_assert_eq!(allowance, expected);
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn random_actions_keep_invariants(ops in prop::collection::vec(op(), 1..40)) {
+        let system = System::new();
+        let program = init_token(&system, Config::default());
+        let mut model = Model {
+            total_supply: 1_000,
+            ..Default::default()
+        };
+
+        for op in &ops {
+            let actor = op.actor();
+            let result = program.send(actor, op.action());
+            match model.apply(op) {
+                Expected::Panic => prop_assert!(result.main_failed(), "{:?} must fail", op),
+                Expected::Reply(reply) => {
+                    prop_assert!(
+                        result.contains(&Log::builder().dest(actor).payload(reply)),
+                        "unexpected reply to {:?}",
+                        op
+                    );
+                }
+            }
+            check_state(&program, &model)?;
+        }
+    }
+}
@@ -9,7 +9,7 @@ pub struct NftMetadata;
 
 impl Metadata for NftMetadata {
     type Init = In<InitNft>;
-    type Handle = InOut<NftAction, NftEvent>;
+    type Handle = InOut<NftAction, Result<NftEvent, NftError>>;
     type Reply = ();
     type Others = ();
     type Signal = ();
@@ -19,6 +19,55 @@ impl Metadata for NftMetadata {
 #[derive(Default, Debug, Encode, Decode, TypeInfo)]
 pub struct Config {
     pub max_mint_count: Option<u128>,
+    pub mint_policy: MintPolicy,
+    /// Default royalty of the collection's tokens.
+    pub royalty: Option<Royalty>,
+    /// Sales for `NftAction::MintWithProof`; the first one in progress applies.
+    pub sale_phases: Vec<SalePhase>,
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+pub struct SalePhase {
+    /// Block timestamps in milliseconds; `end` is exclusive.
+    pub start: u64,
+    pub end: u64,
+    /// Native value to attach to each mint.
+    pub price: u128,
+    pub max_per_wallet: Option<u32>,
+    /// Root of the merkle tree of allowed accounts; anyone may mint if `None`.
+    pub merkle_root: Option<[u8; 32]>,
+}
+
+/// Share of a sale price owed to `receiver`, in basis points (1/100 of a percent).
+#[derive(Debug, Encode, Decode, TypeInfo, Clone, Copy)]
+pub struct Royalty {
+    pub receiver: ActorId,
+    pub basis_points: u16,
+}
+
+/// Who may call `NftAction::Mint`. The collection owner can always mint for free.
+#[derive(Default, Debug, Encode, Decode, TypeInfo, Clone)]
+pub enum MintPolicy {
+    #[default]
+    OwnerOnly,
+    /// Accounts added with `NftAction::AddMinter` mint for free.
+    Allowlist,
+    /// Anyone mints by paying `price`, at most `max_per_wallet` tokens each.
+    Public {
+        price: MintPrice,
+        max_per_wallet: Option<u32>,
+    },
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+pub enum MintPrice {
+    /// Attached to the mint message as value.
+    Native(u128),
+    /// Transferred from the minter, who must approve this program first.
+    Token {
+        ft_program_id: ActorId,
+        amount: u128,
+    },
 }
 
 #[derive(Default, Debug, Encode, Decode, TypeInfo)]
@@ -31,6 +80,13 @@ pub struct InitNft {
 pub struct Collection {
     pub name: String,
     pub description: String,
+    /// Tokens can't be transferred or approved; the collection owner may burn them.
+    pub soulbound: bool,
+    /// Prefix of token URIs, followed by `TokenMetadata::uri_suffix` or the token id;
+    /// when empty, token URIs are the full `TokenMetadata::media`.
+    pub base_uri: String,
+    /// URI of every token until `NftAction::Reveal`.
+    pub placeholder_uri: Option<String>,
 }
 
 #[derive(Debug, Encode, Decode, TypeInfo)]
@@ -57,6 +113,132 @@ pub enum NftAction {
         to: ActorId,
         token_id: TokenId,
     },
+    AddMinter {
+        minter: ActorId,
+    },
+    RemoveMinter {
+        minter: ActorId,
+    },
+    /// Sends the collected mint payments to the collection owner.
+    WithdrawProceeds,
+    /// Overrides the collection royalty for one token; `None` restores the default.
+    SetTokenRoyalty {
+        token_id: TokenId,
+        royalty: Option<Royalty>,
+    },
+    GetRoyaltyInfo {
+        token_id: TokenId,
+        sale_price: u128,
+    },
+    /// Allows or forbids `operator` to manage all tokens of the sender.
+    SetApprovalForAll {
+        operator: ActorId,
+        approved: bool,
+    },
+    RevokeApproval {
+        token_id: TokenId,
+    },
+    IsApprovedForAll {
+        owner: ActorId,
+        operator: ActorId,
+    },
+    /// Mints all tokens or none; not available for paid mints.
+    BatchMint {
+        to_and_metadata: Vec<(ActorId, TokenMetadata)>,
+    },
+    /// Transfers all tokens or none.
+    BatchTransfer {
+        transfers: Vec<(ActorId, TokenId)>,
+    },
+    /// Allows `signer` to sign mint vouchers.
+    AddVoucherSigner {
+        signer: ActorId,
+    },
+    RemoveVoucherSigner {
+        signer: ActorId,
+    },
+    /// Mints the voucher's token to the sender, who attaches the voucher price as value.
+    RedeemVoucher {
+        voucher: MintVoucher,
+        signature: [u8; 64],
+    },
+    /// Allows `updater` to update token metadata.
+    AddMetadataUpdater {
+        updater: ActorId,
+    },
+    RemoveMetadataUpdater {
+        updater: ActorId,
+    },
+    /// Replaces the metadata of a token that is not frozen.
+    UpdateMetadata {
+        token_id: TokenId,
+        token_metadata: TokenMetadata,
+    },
+    /// Permanently forbids metadata updates of a token. Only the collection
+    /// owner and metadata updaters may freeze it.
+    FreezeMetadata {
+        token_id: TokenId,
+    },
+    /// Lets `user` use a token until the block timestamp `expires`, in milliseconds.
+    /// `ZERO_ID` removes the user.
+    SetUser {
+        token_id: TokenId,
+        user: ActorId,
+        expires: u64,
+    },
+    /// Replaces the placeholder URI with the final base URI.
+    Reveal {
+        base_uri: String,
+    },
+    /// Transfers a token to a program that acknowledges it with `NftReceiverReply::Received`.
+    ///
+    /// The token is locked until the receiver replies and returned to its owner if the
+    /// receiver refuses it or times out. User accounts cannot reply, so a safe transfer
+    /// to one always fails; use `Transfer` for them.
+    SafeTransfer {
+        to: ActorId,
+        token_id: TokenId,
+        data: Vec<u8>,
+    },
+    /// Creates a token id with many identical copies sharing `token_metadata`.
+    CreateEdition {
+        token_metadata: TokenMetadata,
+    },
+    MintEdition {
+        token_id: TokenId,
+        to: ActorId,
+        amount: u128,
+    },
+    /// Transfers `(token_id, amount)` pairs of editions, all or none.
+    TransferEditions {
+        from: ActorId,
+        to: ActorId,
+        editions: Vec<(TokenId, u128)>,
+    },
+    /// Mints a token to the sender during the current sale phase.
+    /// `proof` shows that the sender is in the phase allowlist, if there is one.
+    MintWithProof {
+        token_metadata: TokenMetadata,
+        proof: Vec<[u8; 32]>,
+    },
+}
+
+/// Off-chain permission to mint one token.
+///
+/// `signer` signs the SCALE encoding of `(program_id, voucher)` with sr25519
+/// under the `substrate` signing context.
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+pub struct MintVoucher {
+    pub signer: ActorId,
+    /// Distinguishes vouchers of the same signer; each can be redeemed once.
+    pub nonce: u64,
+    pub token_metadata: TokenMetadata,
+    /// Native value to attach when redeeming.
+    pub price: u128,
+    /// The only account allowed to redeem the voucher, if any.
+    pub recipient: Option<ActorId>,
+    /// Block timestamp in milliseconds after which the voucher is void.
+    pub expires_at: u64,
 }
 
 #[derive(Debug, Encode, Decode, TypeInfo)]
@@ -87,6 +269,140 @@ pub enum NftEvent {
         token_id: TokenId,
         approved: bool,
     },
+    MinterAdded {
+        minter: ActorId,
+    },
+    MinterRemoved {
+        minter: ActorId,
+    },
+    /// `tokens` is zero if the token program refused the transfer.
+    ProceedsWithdrawn {
+        native: u128,
+        tokens: u128,
+    },
+    TokenRoyaltySet {
+        token_id: TokenId,
+        royalty: Option<Royalty>,
+    },
+    /// `None` if the token has no royalty.
+    RoyaltyInfo(Option<(ActorId, u128)>),
+    ApprovalForAll {
+        owner: ActorId,
+        operator: ActorId,
+        approved: bool,
+    },
+    ApprovalRevoked {
+        token_id: TokenId,
+    },
+    /// Receivers with the ids of the tokens minted for them.
+    BatchMinted {
+        minted: Vec<(ActorId, TokenId)>,
+    },
+    /// `(from, to, token_id)` of every transferred token.
+    BatchTransferred {
+        transfers: Vec<(ActorId, ActorId, TokenId)>,
+    },
+    VoucherSignerAdded {
+        signer: ActorId,
+    },
+    VoucherSignerRemoved {
+        signer: ActorId,
+    },
+    VoucherRedeemed {
+        signer: ActorId,
+        nonce: u64,
+        to: ActorId,
+        token_id: TokenId,
+    },
+    MetadataUpdaterAdded {
+        updater: ActorId,
+    },
+    MetadataUpdaterRemoved {
+        updater: ActorId,
+    },
+    MetadataUpdated {
+        token_id: TokenId,
+        token_metadata: TokenMetadata,
+    },
+    MetadataFrozen {
+        token_id: TokenId,
+    },
+    UserSet {
+        token_id: TokenId,
+        user: ActorId,
+        expires: u64,
+    },
+    Revealed {
+        base_uri: String,
+    },
+    EditionCreated {
+        token_id: TokenId,
+        token_metadata: TokenMetadata,
+    },
+    EditionMinted {
+        token_id: TokenId,
+        to: ActorId,
+        amount: u128,
+    },
+    EditionsTransferred {
+        from: ActorId,
+        to: ActorId,
+        editions: Vec<(TokenId, u128)>,
+    },
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo, Clone)]
+pub enum NftError {
+    TokenDoesNotExist,
+    EditionDoesNotExist,
+    AccessDenied,
+    ZeroAddress,
+    ApprovalToOwner,
+    ApprovalAlreadyIssued,
+    MaxMintCountReached,
+    WrongPrice,
+    MintLimitReached,
+    PaidMintNotSupported,
+    RoyaltyTooHigh,
+    InvalidSalePhase,
+    SignerNotAuthorized,
+    InvalidSignature,
+    VoucherAlreadyRedeemed,
+    VoucherExpired,
+    WrongRecipient,
+    MetadataFrozen,
+    Soulbound,
+    InvalidExpiry,
+    AlreadyRevealed,
+    EmptyBaseUri,
+    InsufficientEditionBalance,
+    ArithmeticOverflow,
+    NoActiveSalePhase,
+    NotInAllowlist,
+    DuplicateToken,
+    /// The token awaits the acknowledgement of a safe transfer.
+    TokenLocked,
+    /// The receiver did not acknowledge the token, which was returned to its owner.
+    SafeTransferRejected,
+    /// A payment to or from the program failed; nothing was minted or withdrawn.
+    PaymentFailed,
+}
+
+/// Message sent to the receiver of `NftAction::SafeTransfer`.
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum NftReceiverAction {
+    OnNftReceived {
+        operator: ActorId,
+        from: ActorId,
+        token_id: TokenId,
+        data: Vec<u8>,
+    },
+}
+
+/// Reply of a receiver accepting the token.
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum NftReceiverReply {
+    Received { token_id: TokenId },
 }
 
 #[derive(Default, Debug, Encode, Decode, TypeInfo, Clone)]
@@ -97,8 +413,12 @@ pub struct TokenMetadata {
     pub description: String,
     // URL to associated media, preferably to decentralized, content-addressed storage
     pub media: String,
+    // token URI after the collection base URI, defaults to the token id
+    pub uri_suffix: Option<String>,
     // URL to an off-chain JSON file with more info.
     pub reference: String,
+    // key/value pairs, ex. ("level", "3")
+    pub attributes: Vec<(String, String)>,
 }
 
 #[derive(Default, Debug, Encode, Decode, TypeInfo)]
@@ -111,6 +431,21 @@ pub struct State {
     pub owner: ActorId,
     pub collection: Collection,
     pub config: Config,
+    pub minters: Vec<ActorId>,
+    pub mints_by_wallet: Vec<(ActorId, u32)>,
+    pub native_proceeds: u128,
+    pub token_proceeds: u128,
+    pub token_royalties: Vec<(TokenId, Royalty)>,
+    pub operators: Vec<(ActorId, Vec<ActorId>)>,
+    pub voucher_signers: Vec<ActorId>,
+    pub used_vouchers: Vec<(ActorId, u64)>,
+    pub metadata_updaters: Vec<ActorId>,
+    pub frozen_metadata: Vec<TokenId>,
+    pub token_users: Vec<(TokenId, (ActorId, u64))>,
+    pub edition_supply: Vec<(TokenId, u128)>,
+    pub edition_balances: Vec<((TokenId, ActorId), u128)>,
+    /// Mints by sale phase index and account.
+    pub phase_mints: Vec<((u32, ActorId), u32)>,
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -120,10 +455,67 @@ pub enum StateQuery {
     Collection,
     Owner,
     CurrentTokenId,
-    OwnerById { token_id: TokenId },
-    TokenApprovals { token_id: TokenId },
-    TokenMetadata { token_id: TokenId },
-    OwnerTokens { owner: ActorId },
+    OwnerById {
+        token_id: TokenId,
+    },
+    TokenApprovals {
+        token_id: TokenId,
+    },
+    TokenMetadata {
+        token_id: TokenId,
+    },
+    OwnerTokens {
+        owner: ActorId,
+    },
+    RoyaltyInfo {
+        token_id: TokenId,
+        sale_price: u128,
+    },
+    IsApprovedForAll {
+        owner: ActorId,
+        operator: ActorId,
+    },
+    Operators {
+        owner: ActorId,
+    },
+    VoucherSigners,
+    IsVoucherUsed {
+        signer: ActorId,
+        nonce: u64,
+    },
+    IsMetadataFrozen {
+        token_id: TokenId,
+    },
+    UserOf {
+        token_id: TokenId,
+    },
+    TokenUri {
+        token_id: TokenId,
+    },
+    TotalSupply,
+    TokenByIndex {
+        index: u128,
+    },
+    TokensPaginated {
+        offset: u32,
+        limit: u32,
+    },
+    OwnerTokensPaginated {
+        owner: ActorId,
+        offset: u32,
+        limit: u32,
+    },
+    EditionSupply {
+        token_id: TokenId,
+    },
+    EditionBalance {
+        token_id: TokenId,
+        owner: ActorId,
+    },
+    CurrentPhase,
+    RemainingAllocation {
+        account: ActorId,
+    },
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -137,4 +529,61 @@ pub enum StateReply {
     TokenApprovals(Option<ActorId>),
     TokenMetadata(Option<TokenMetadata>),
     OwnerTokens(Option<Vec<TokenId>>),
-}
\ No newline at end of file
+    RoyaltyInfo(Option<(ActorId, u128)>),
+    IsApprovedForAll(bool),
+    Operators(Vec<ActorId>),
+    VoucherSigners(Vec<ActorId>),
+    IsVoucherUsed(bool),
+    IsMetadataFrozen(bool),
+    /// `None` if the token has no user or the rental expired.
+    UserOf(Option<ActorId>),
+    TokenUri(Option<String>),
+    TotalSupply(u128),
+    TokenByIndex(Option<TokenId>),
+    /// Tokens in global index order.
+    TokensPaginated(Vec<TokenId>),
+    /// Tokens of the owner in ascending id order.
+    OwnerTokensPaginated(Vec<TokenId>),
+    /// `None` if the token id is not an edition.
+    EditionSupply(Option<u128>),
+    EditionBalance(u128),
+    /// Index and parameters of the sale phase in progress, if any.
+    CurrentPhase(Option<(u32, SalePhase)>),
+    /// Mints left in the current phase; zero if no phase is in progress and
+    /// `None` if the phase has no per-wallet cap.
+    RemainingAllocation(Option<u32>),
+}
+
+/// Messages of the fungible token used for `MintPrice::Token`.
+#[derive(Debug, Decode, Encode, TypeInfo)]
+pub enum FTAction {
+    Mint(u128),
+    Burn(u128),
+    Transfer {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+    Approve {
+        to: ActorId,
+        amount: u128,
+    },
+    TotalSupply,
+    BalanceOf(ActorId),
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum FTEvent {
+    Transfer {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+    Approve {
+        from: ActorId,
+        to: ActorId,
+        amount: u128,
+    },
+    TotalSupply(u128),
+    Balance(u128),
+}
@@ -0,0 +1,28 @@
+use gstd::{msg, ActorId};
+use io::{FTAction, FTEvent};
+
+/// Transfers `amount` tokens of `ft_program_id` from `from` to `to`.
+pub async fn transfer_tokens(
+    ft_program_id: &ActorId,
+    from: &ActorId,
+    to: &ActorId,
+    amount: u128,
+) -> Result<(), ()> {
+    let reply = msg::send_for_reply_as::<_, FTEvent>(
+        *ft_program_id,
+        FTAction::Transfer {
+            from: *from,
+            to: *to,
+            amount,
+        },
+        0,
+        0,
+    )
+    .expect("Error in sending a message `FTAction::Transfer`")
+    .await;
+
+    match reply {
+        Ok(FTEvent::Transfer { .. }) => Ok(()),
+        _ => Err(()),
+    }
+}
@@ -1,32 +1,78 @@
-
 #![no_std]
 
+use ft_messages::*;
 use gstd::{
-    collections::{HashMap, HashSet},
-    msg,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    exec, msg,
     prelude::*,
     ActorId,
 };
 use io::*;
+use receiver_messages::*;
+
+#[cfg(test)]
+extern crate std;
+
+mod ft_messages;
+mod merkle;
+mod receiver_messages;
+mod vouchers;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Default)]
 pub struct Nft {
     pub owner_by_id: HashMap<TokenId, ActorId>,
     pub token_approvals: HashMap<TokenId, ActorId>,
     pub token_metadata_by_id: HashMap<TokenId, TokenMetadata>,
-    pub tokens_for_owner: HashMap<ActorId, HashSet<TokenId>>,
+    pub tokens_for_owner: HashMap<ActorId, BTreeSet<TokenId>>,
+    /// Existing tokens; burning moves the last token into the freed index.
+    pub all_tokens: Vec<TokenId>,
+    pub all_tokens_index: HashMap<TokenId, usize>,
     pub token_id: TokenId,
     pub owner: ActorId,
     pub collection: Collection,
     pub config: Config,
+    pub minters: HashSet<ActorId>,
+    pub mints_by_wallet: HashMap<ActorId, u32>,
+    /// Public mints whose token payment is still awaited.
+    pub pending_mints: u128,
+    pub native_proceeds: u128,
+    pub token_proceeds: u128,
+    pub token_royalties: HashMap<TokenId, Royalty>,
+    /// Accounts allowed to manage all tokens of an owner.
+    pub operators: HashMap<ActorId, HashSet<ActorId>>,
+    pub voucher_signers: HashSet<ActorId>,
+    /// Redeemed vouchers by signer and nonce.
+    pub used_vouchers: HashSet<(ActorId, u64)>,
+    /// Accounts allowed to update token metadata besides the collection owner.
+    pub metadata_updaters: HashSet<ActorId>,
+    pub frozen_metadata: HashSet<TokenId>,
+    /// Users of rented tokens with the block timestamp their rental expires at.
+    pub token_users: HashMap<TokenId, (ActorId, u64)>,
+    /// Minted copies of each edition; its metadata is kept in `token_metadata_by_id`.
+    pub edition_supply: HashMap<TokenId, u128>,
+    pub edition_balances: HashMap<(TokenId, ActorId), u128>,
+    /// Mints by sale phase index and account.
+    pub phase_mints: HashMap<(u32, ActorId), u32>,
+    /// Tokens awaiting the receiver's acknowledgement of a safe transfer.
+    pub locked_tokens: HashSet<TokenId>,
 }
 
+/// Royalty basis points of a full sale price.
+const MAX_ROYALTY_BASIS_POINTS: u16 = 10_000;
+
 static mut NFT: Option<Nft> = None;
 
 #[no_mangle]
 unsafe extern fn init() {
     let init: InitNft = msg::load().expect("Unable to decode InitNft");
 
+    if let Err(error) = validate_config(&init.config) {
+        panic!("Invalid InitNft: {error:?}");
+    }
+
     let nft = Nft {
         collection: init.collection,
         config: init.config,
@@ -36,20 +82,209 @@ unsafe extern fn init() {
     NFT = Some(nft);
 }
 
+fn validate_config(config: &Config) -> Result<(), NftError> {
+    if let Some(royalty) = &config.royalty {
+        check_royalty(royalty)?;
+    }
+    if config
+        .sale_phases
+        .iter()
+        .any(|phase| phase.start >= phase.end)
+    {
+        return Err(NftError::InvalidSalePhase);
+    }
+    Ok(())
+}
+
+/// Check that no value is attached to a free mint, where it would be lost
+fn check_free_mint() -> Result<(), NftError> {
+    if msg::value() != 0 {
+        return Err(NftError::WrongPrice);
+    }
+    Ok(())
+}
+
+/// Check that a royalty does not exceed the sale price
+fn check_royalty(royalty: &Royalty) -> Result<(), NftError> {
+    if royalty.basis_points > MAX_ROYALTY_BASIS_POINTS {
+        return Err(NftError::RoyaltyTooHigh);
+    }
+    Ok(())
+}
+
 impl Nft {
-    /// Mint a new nft using `TokenMetadata`
-    fn mint(&mut self, to: &ActorId, token_metadata: TokenMetadata) -> NftEvent {
-        self.check_config();
-        self.check_zero_address(to);
+    /// Mint a new nft using `TokenMetadata`, charging the public mint price if any
+    async fn mint(
+        &mut self,
+        to: &ActorId,
+        token_metadata: TokenMetadata,
+    ) -> Result<NftEvent, NftError> {
+        self.check_config(1)?;
+        self.check_zero_address(to)?;
+        let minter = msg::source();
+        match self.mint_price(&minter)? {
+            None => check_free_mint()?,
+            Some(MintPrice::Native(price)) => {
+                if msg::value() != price {
+                    return Err(NftError::WrongPrice);
+                }
+                *self.mints_by_wallet.entry(minter).or_default() += 1;
+                self.native_proceeds += price;
+            }
+            Some(MintPrice::Token {
+                ft_program_id,
+                amount,
+            }) => {
+                // Reserve the mint while the payment is in flight so that
+                // concurrent mints cannot exceed the limits.
+                *self.mints_by_wallet.entry(minter).or_default() += 1;
+                self.pending_mints += 1;
+                let payment =
+                    transfer_tokens(&ft_program_id, &minter, &exec::program_id(), amount).await;
+                self.pending_mints -= 1;
+                if payment.is_err() {
+                    self.mints_by_wallet
+                        .entry(minter)
+                        .and_modify(|count| *count -= 1);
+                    return Err(NftError::PaymentFailed);
+                }
+                self.token_proceeds += amount;
+            }
+        }
+        Ok(self.mint_token(to, token_metadata))
+    }
+    /// Mint several tokens at once for free, either all or none of them
+    fn batch_mint(
+        &mut self,
+        to_and_metadata: Vec<(ActorId, TokenMetadata)>,
+    ) -> Result<NftEvent, NftError> {
+        self.check_config(to_and_metadata.len() as u128)?;
+        if self.mint_price(&msg::source())?.is_some() {
+            return Err(NftError::PaidMintNotSupported);
+        }
+        check_free_mint()?;
+        for (to, _) in &to_and_metadata {
+            self.check_zero_address(to)?;
+        }
+        let minted = to_and_metadata
+            .into_iter()
+            .map(|(to, token_metadata)| {
+                let token_id = self.token_id;
+                self.mint_token(&to, token_metadata);
+                (to, token_id)
+            })
+            .collect();
+
+        Ok(NftEvent::BatchMinted { minted })
+    }
+    /// Mint the token of a voucher signed off-chain by an authorized signer
+    fn redeem_voucher(
+        &mut self,
+        voucher: MintVoucher,
+        signature: [u8; 64],
+    ) -> Result<NftEvent, NftError> {
+        let to = msg::source();
+        if !self.voucher_signers.contains(&voucher.signer) {
+            return Err(NftError::SignerNotAuthorized);
+        }
+        if !vouchers::is_signed(&voucher, &signature) {
+            return Err(NftError::InvalidSignature);
+        }
+        if self
+            .used_vouchers
+            .contains(&(voucher.signer, voucher.nonce))
+        {
+            return Err(NftError::VoucherAlreadyRedeemed);
+        }
+        if exec::block_timestamp() > voucher.expires_at {
+            return Err(NftError::VoucherExpired);
+        }
+        if voucher.recipient.is_some_and(|recipient| recipient != to) {
+            return Err(NftError::WrongRecipient);
+        }
+        if msg::value() != voucher.price {
+            return Err(NftError::WrongPrice);
+        }
+        self.check_config(1)?;
+
+        self.used_vouchers.insert((voucher.signer, voucher.nonce));
+        self.native_proceeds += voucher.price;
+        let token_id = self.token_id;
+        self.mint_token(&to, voucher.token_metadata);
+
+        Ok(NftEvent::VoucherRedeemed {
+            signer: voucher.signer,
+            nonce: voucher.nonce,
+            to,
+            token_id,
+        })
+    }
+    /// Mint a token for `msg::source()` during the current sale phase
+    fn mint_with_proof(
+        &mut self,
+        token_metadata: TokenMetadata,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<NftEvent, NftError> {
+        let minter = msg::source();
+        let (index, phase) = self.current_phase().ok_or(NftError::NoActiveSalePhase)?;
+        if let Some(merkle_root) = &phase.merkle_root {
+            if !merkle::verify(merkle_root, &minter, &proof) {
+                return Err(NftError::NotInAllowlist);
+            }
+        }
+        if self.remaining_allocation(index, phase, &minter) == Some(0) {
+            return Err(NftError::MintLimitReached);
+        }
+        let price = phase.price;
+        if msg::value() != price {
+            return Err(NftError::WrongPrice);
+        }
+        self.check_config(1)?;
+
+        self.native_proceeds += price;
+        *self.phase_mints.entry((index, minter)).or_default() += 1;
+        Ok(self.mint_token(&minter, token_metadata))
+    }
+    /// Get the index and parameters of the first sale phase in progress
+    fn current_phase(&self) -> Option<(u32, &SalePhase)> {
+        let now = exec::block_timestamp();
+        self.config
+            .sale_phases
+            .iter()
+            .enumerate()
+            .find(|(_, phase)| phase.start <= now && now < phase.end)
+            .map(|(index, phase)| (index as u32, phase))
+    }
+    /// Get how many tokens `account` can still mint in a phase, `None` if unlimited
+    fn remaining_allocation(
+        &self,
+        index: u32,
+        phase: &SalePhase,
+        account: &ActorId,
+    ) -> Option<u32> {
+        let minted = self
+            .phase_mints
+            .get(&(index, *account))
+            .copied()
+            .unwrap_or_default();
+        phase
+            .max_per_wallet
+            .map(|max_per_wallet| max_per_wallet.saturating_sub(minted))
+    }
+    /// Stores a new token for `to`
+    fn mint_token(&mut self, to: &ActorId, token_metadata: TokenMetadata) -> NftEvent {
         self.owner_by_id.insert(self.token_id, *to);
         self.tokens_for_owner
             .entry(*to)
             .and_modify(|tokens| {
                 tokens.insert(self.token_id);
             })
-            .or_insert_with(|| HashSet::from([self.token_id]));
+            .or_insert_with(|| BTreeSet::from([self.token_id]));
         self.token_metadata_by_id
             .insert(self.token_id, token_metadata.clone());
+        self.all_tokens_index
+            .insert(self.token_id, self.all_tokens.len());
+        self.all_tokens.push(self.token_id);
 
         self.token_id += 1;
 
@@ -59,13 +294,14 @@ impl Nft {
         }
     }
     /// Burn nft by `TokenId`
-    fn burn(&mut self, token_id: TokenId) -> NftEvent {
-        let owner = *self
-            .owner_by_id
-            .get(&token_id)
-            .expect("NonFungibleToken: token does not exist");
+    fn burn(&mut self, token_id: TokenId) -> Result<NftEvent, NftError> {
+        let owner = self.token_owner(token_id)?;
+        self.check_unlocked(token_id)?;
 
-        self.check_owner(&owner);
+        // The collection owner may revoke soulbound tokens it issued.
+        if !(self.collection.soulbound && msg::source() == self.owner) {
+            self.check_owner(&owner)?;
+        }
         self.owner_by_id.remove(&token_id);
         self.token_metadata_by_id.remove(&token_id);
 
@@ -75,147 +311,654 @@ impl Nft {
                 self.tokens_for_owner.remove(&owner);
             }
         }
+        if let Some(index) = self.all_tokens_index.remove(&token_id) {
+            self.all_tokens.swap_remove(index);
+            if let Some(moved) = self.all_tokens.get(index) {
+                self.all_tokens_index.insert(*moved, index);
+            }
+        }
         self.token_approvals.remove(&token_id);
+        self.token_royalties.remove(&token_id);
+        self.frozen_metadata.remove(&token_id);
+        self.token_users.remove(&token_id);
 
-        NftEvent::Burnt { token_id }
+        Ok(NftEvent::Burnt { token_id })
     }
     ///  Transfer token from `token_id` to address `to`
-    fn transfer(&mut self, to: &ActorId, token_id: TokenId) -> NftEvent {
-        let owner = *self
-            .owner_by_id
-            .get(&token_id)
-            .expect("NonFungibleToken: token does not exist");
+    fn transfer(&mut self, to: &ActorId, token_id: TokenId) -> Result<NftEvent, NftError> {
+        let owner = self.check_transfer(to, token_id)?;
+        self.move_token(&owner, to, token_id);
 
-        self.can_transfer(token_id, &owner);
-        self.check_zero_address(to);
+        Ok(NftEvent::Transferred {
+            from: owner,
+            to: *to,
+            token_id,
+        })
+    }
+    /// Transfer `token_id` to `to` and revert it unless `to` acknowledges it
+    async fn safe_transfer(
+        &mut self,
+        to: &ActorId,
+        token_id: TokenId,
+        data: Vec<u8>,
+    ) -> Result<NftEvent, NftError> {
+        let from = self.check_transfer(to, token_id)?;
+        let approval = self.token_approvals.get(&token_id).copied();
+        let user = self.token_users.get(&token_id).copied();
+        self.move_token(&from, to, token_id);
+        self.locked_tokens.insert(token_id);
+        let accepted = on_nft_received(to, &msg::source(), &from, token_id, data).await;
+        self.locked_tokens.remove(&token_id);
+        if !accepted {
+            // An error after the await would not undo the transfer, so the token,
+            // which could not leave `to` while locked, is moved back by hand
+            // together with the approval and the user the transfer cleared.
+            self.move_token(to, &from, token_id);
+            if let Some(approval) = approval {
+                self.token_approvals.insert(token_id, approval);
+            }
+            if let Some(user) = user {
+                self.token_users.insert(token_id, user);
+            }
+            return Err(NftError::SafeTransferRejected);
+        }
+        Ok(NftEvent::Transferred {
+            from,
+            to: *to,
+            token_id,
+        })
+    }
+    /// Reassign `token_id` from `owner` to `to`
+    fn move_token(&mut self, owner: &ActorId, to: &ActorId, token_id: TokenId) {
         // assign new owner
         self.owner_by_id
             .entry(token_id)
             .and_modify(|owner| *owner = *to);
+        // remove token from old owner first, so a transfer to itself keeps it
+        if let Some(tokens) = self.tokens_for_owner.get_mut(owner) {
+            tokens.remove(&token_id);
+            if tokens.is_empty() {
+                self.tokens_for_owner.remove(owner);
+            }
+        }
         // push token to new owner
         self.tokens_for_owner
             .entry(*to)
             .and_modify(|tokens| {
                 tokens.insert(token_id);
             })
-            .or_insert_with(|| HashSet::from([token_id]));
-        // remove token from old owner
-        if let Some(tokens) = self.tokens_for_owner.get_mut(&owner) {
-            tokens.remove(&token_id);
-            if tokens.is_empty() {
-                self.tokens_for_owner.remove(&owner);
+            .or_insert_with(|| BTreeSet::from([token_id]));
+        // remove approvals and the user if any
+        self.token_approvals.remove(&token_id);
+        self.token_users.remove(&token_id);
+    }
+    /// Transfer several tokens, either all or none of them
+    fn batch_transfer(&mut self, transfers: Vec<(ActorId, TokenId)>) -> Result<NftEvent, NftError> {
+        // Nothing may change before every transfer is known to succeed.
+        let mut token_ids = BTreeSet::new();
+        let mut owners = Vec::with_capacity(transfers.len());
+        for (to, token_id) in &transfers {
+            if !token_ids.insert(*token_id) {
+                return Err(NftError::DuplicateToken);
             }
+            owners.push(self.check_transfer(to, *token_id)?);
         }
-        // remove approvals if any
-        self.token_approvals.remove(&token_id);
+        let transfers = transfers
+            .into_iter()
+            .zip(owners)
+            .map(|((to, token_id), owner)| {
+                self.move_token(&owner, &to, token_id);
+                (owner, to, token_id)
+            })
+            .collect();
 
-        NftEvent::Transferred {
-            from: owner,
-            to: *to,
-            token_id,
-        }
+        Ok(NftEvent::BatchTransferred { transfers })
     }
     ///  Approve token from `token_id` to address `to`
-    fn approve(&mut self, to: &ActorId, token_id: TokenId) -> NftEvent {
-        let owner = self
-            .owner_by_id
-            .get(&token_id)
-            .expect("NonFungibleToken: token does not exist");
-        self.check_owner(owner);
-        self.check_zero_address(to);
-        self.check_approve(&token_id);
+    fn approve(&mut self, to: &ActorId, token_id: TokenId) -> Result<NftEvent, NftError> {
+        let owner = self.token_owner(token_id)?;
+        self.check_unlocked(token_id)?;
+        self.check_transferable()?;
+        self.check_owner_or_operator(&owner)?;
+        self.check_zero_address(to)?;
+        self.check_approve(&token_id)?;
         self.token_approvals.insert(token_id, *to);
 
-        NftEvent::Approved {
-            owner: *owner,
+        Ok(NftEvent::Approved {
+            owner,
             approved_account: *to,
             token_id,
+        })
+    }
+    /// Allow or forbid `operator` to manage all tokens of `msg::source()`
+    fn set_approval_for_all(
+        &mut self,
+        operator: &ActorId,
+        approved: bool,
+    ) -> Result<NftEvent, NftError> {
+        let owner = msg::source();
+        if approved {
+            self.check_transferable()?;
+        }
+        self.check_zero_address(operator)?;
+        if operator == &owner {
+            return Err(NftError::ApprovalToOwner);
         }
+        if approved {
+            self.operators.entry(owner).or_default().insert(*operator);
+        } else if let Some(operators) = self.operators.get_mut(&owner) {
+            operators.remove(operator);
+            if operators.is_empty() {
+                self.operators.remove(&owner);
+            }
+        }
+        Ok(NftEvent::ApprovalForAll {
+            owner,
+            operator: *operator,
+            approved,
+        })
     }
-    /// Get `ActorId` of the nft owner with `token_id`
-    fn owner(&self, token_id: TokenId) -> NftEvent {
-        let owner = self
-            .owner_by_id
+    /// Remove the approval issued for `token_id`
+    fn revoke_approval(&mut self, token_id: TokenId) -> Result<NftEvent, NftError> {
+        let owner = self.token_owner(token_id)?;
+        self.check_owner_or_operator(&owner)?;
+        self.token_approvals.remove(&token_id);
+        Ok(NftEvent::ApprovalRevoked { token_id })
+    }
+    /// Check whether `operator` manages all tokens of `owner`
+    fn is_operator(&self, owner: &ActorId, operator: &ActorId) -> bool {
+        self.operators
+            .get(owner)
+            .is_some_and(|operators| operators.contains(operator))
+    }
+    /// Let `user` use `token_id` until `expires`
+    fn set_user(
+        &mut self,
+        token_id: TokenId,
+        user: &ActorId,
+        expires: u64,
+    ) -> Result<NftEvent, NftError> {
+        let owner = self.token_owner(token_id)?;
+        self.can_transfer(token_id, &owner)?;
+        if user == &ZERO_ID {
+            self.token_users.remove(&token_id);
+        } else {
+            if expires <= exec::block_timestamp() {
+                return Err(NftError::InvalidExpiry);
+            }
+            self.token_users.insert(token_id, (*user, expires));
+        }
+        Ok(NftEvent::UserSet {
+            token_id,
+            user: *user,
+            expires,
+        })
+    }
+    /// Get the current user of `token_id`, if the rental has not expired
+    fn user_of(&self, token_id: TokenId) -> Option<ActorId> {
+        self.token_users
             .get(&token_id)
-            .expect("NonFungibleToken: token does not exist");
+            .filter(|(_, expires)| *expires > exec::block_timestamp())
+            .map(|(user, _)| *user)
+    }
+    /// Create an edition whose copies share `token_metadata`
+    fn create_edition(&mut self, token_metadata: TokenMetadata) -> Result<NftEvent, NftError> {
+        self.check_config(1)?;
+        if self.mint_price(&msg::source())?.is_some() {
+            return Err(NftError::PaidMintNotSupported);
+        }
+        let token_id = self.token_id;
+        self.token_metadata_by_id
+            .insert(token_id, token_metadata.clone());
+        self.edition_supply.insert(token_id, 0);
+        self.token_id += 1;
 
-        NftEvent::Owner {
-            owner: *owner,
+        Ok(NftEvent::EditionCreated {
             token_id,
+            token_metadata,
+        })
+    }
+    /// Mint `amount` copies of the edition `token_id` for `to`
+    fn mint_edition(
+        &mut self,
+        token_id: TokenId,
+        to: &ActorId,
+        amount: u128,
+    ) -> Result<NftEvent, NftError> {
+        if self.mint_price(&msg::source())?.is_some() {
+            return Err(NftError::PaidMintNotSupported);
         }
+        check_free_mint()?;
+        self.check_zero_address(to)?;
+        let supply = self
+            .edition_supply
+            .get_mut(&token_id)
+            .ok_or(NftError::EditionDoesNotExist)?;
+        *supply = supply
+            .checked_add(amount)
+            .ok_or(NftError::ArithmeticOverflow)?;
+        *self.edition_balances.entry((token_id, *to)).or_default() += amount;
+
+        Ok(NftEvent::EditionMinted {
+            token_id,
+            to: *to,
+            amount,
+        })
+    }
+    /// Transfer copies of several editions from `from` to `to`, either all or none of them
+    fn transfer_editions(
+        &mut self,
+        from: &ActorId,
+        to: &ActorId,
+        editions: Vec<(TokenId, u128)>,
+    ) -> Result<NftEvent, NftError> {
+        self.check_transferable()?;
+        self.check_owner_or_operator(from)?;
+        self.check_zero_address(to)?;
+        // Nothing may change before every transfer is known to succeed.
+        let mut totals: BTreeMap<TokenId, u128> = BTreeMap::new();
+        for (token_id, amount) in &editions {
+            let total = totals.entry(*token_id).or_default();
+            *total = total
+                .checked_add(*amount)
+                .ok_or(NftError::ArithmeticOverflow)?;
+        }
+        for (token_id, total) in &totals {
+            let balance = self
+                .edition_balances
+                .get(&(*token_id, *from))
+                .copied()
+                .unwrap_or_default();
+            if balance < *total {
+                return Err(NftError::InsufficientEditionBalance);
+            }
+        }
+        for (token_id, total) in totals {
+            if total == 0 {
+                continue;
+            }
+            let balance = self.edition_balances.entry((token_id, *from)).or_default();
+            *balance -= total;
+            if *balance == 0 {
+                self.edition_balances.remove(&(token_id, *from));
+            }
+            *self.edition_balances.entry((token_id, *to)).or_default() += total;
+        }
+
+        Ok(NftEvent::EditionsTransferred {
+            from: *from,
+            to: *to,
+            editions,
+        })
+    }
+    /// Replace the placeholder URI with the final `base_uri`
+    fn reveal(&mut self, base_uri: String) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        if base_uri.is_empty() {
+            return Err(NftError::EmptyBaseUri);
+        }
+        if self.collection.placeholder_uri.take().is_none() {
+            return Err(NftError::AlreadyRevealed);
+        }
+        self.collection.base_uri = base_uri.clone();
+        Ok(NftEvent::Revealed { base_uri })
+    }
+    /// Resolve the effective URI of `token_id`
+    fn token_uri(&self, token_id: TokenId) -> Option<String> {
+        let metadata = self.token_metadata_by_id.get(&token_id)?;
+        if let Some(placeholder_uri) = &self.collection.placeholder_uri {
+            return Some(placeholder_uri.clone());
+        }
+        if self.collection.base_uri.is_empty() {
+            return Some(metadata.media.clone());
+        }
+        let uri = match &metadata.uri_suffix {
+            Some(suffix) => format!("{}{}", self.collection.base_uri, suffix),
+            None => format!("{}{}", self.collection.base_uri, token_id),
+        };
+        Some(uri)
+    }
+    /// Get `ActorId` of the nft owner with `token_id`
+    fn owner(&self, token_id: TokenId) -> Result<NftEvent, NftError> {
+        let owner = self.token_owner(token_id)?;
+
+        Ok(NftEvent::Owner { owner, token_id })
     }
     /// Get confirmation about approval to address `to` and `token_id`
-    fn is_approved_to(&self, to: &ActorId, token_id: TokenId) -> NftEvent {
-        if !self.owner_by_id.contains_key(&token_id) {
-            panic!("Token does not exist")
-        }
-        self.token_approvals.get(&token_id).map_or_else(
-            || NftEvent::CheckIfApproved {
-                to: *to,
-                token_id,
-                approved: false,
-            },
-            |approval_id| NftEvent::CheckIfApproved {
-                to: *to,
-                token_id,
-                approved: *approval_id == *to,
-            },
-        )
+    fn is_approved_to(&self, to: &ActorId, token_id: TokenId) -> Result<NftEvent, NftError> {
+        self.token_owner(token_id)?;
+        let approved = self
+            .token_approvals
+            .get(&token_id)
+            .is_some_and(|approval_id| approval_id == to);
+        Ok(NftEvent::CheckIfApproved {
+            to: *to,
+            token_id,
+            approved,
+        })
+    }
+
+    /// Add an account allowed to mint under `MintPolicy::Allowlist`
+    fn add_minter(&mut self, minter: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.minters.insert(*minter);
+        Ok(NftEvent::MinterAdded { minter: *minter })
+    }
+    /// Remove an account from the minter allowlist
+    fn remove_minter(&mut self, minter: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.minters.remove(minter);
+        Ok(NftEvent::MinterRemoved { minter: *minter })
+    }
+    /// Allow `signer` to sign mint vouchers
+    fn add_voucher_signer(&mut self, signer: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.check_zero_address(signer)?;
+        self.voucher_signers.insert(*signer);
+        Ok(NftEvent::VoucherSignerAdded { signer: *signer })
+    }
+    /// Forbid `signer` to sign mint vouchers, voiding its unredeemed vouchers
+    fn remove_voucher_signer(&mut self, signer: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.voucher_signers.remove(signer);
+        Ok(NftEvent::VoucherSignerRemoved { signer: *signer })
+    }
+    /// Allow `updater` to update token metadata
+    fn add_metadata_updater(&mut self, updater: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.metadata_updaters.insert(*updater);
+        Ok(NftEvent::MetadataUpdaterAdded { updater: *updater })
+    }
+    /// Forbid `updater` to update token metadata
+    fn remove_metadata_updater(&mut self, updater: &ActorId) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.metadata_updaters.remove(updater);
+        Ok(NftEvent::MetadataUpdaterRemoved { updater: *updater })
+    }
+    /// Replace the metadata of `token_id` unless it is frozen
+    fn update_metadata(
+        &mut self,
+        token_id: TokenId,
+        token_metadata: TokenMetadata,
+    ) -> Result<NftEvent, NftError> {
+        self.check_metadata_updater()?;
+        if self.frozen_metadata.contains(&token_id) {
+            return Err(NftError::MetadataFrozen);
+        }
+        let metadata = self
+            .token_metadata_by_id
+            .get_mut(&token_id)
+            .ok_or(NftError::TokenDoesNotExist)?;
+        *metadata = token_metadata.clone();
+
+        Ok(NftEvent::MetadataUpdated {
+            token_id,
+            token_metadata,
+        })
+    }
+    /// Freeze the metadata of `token_id`, by the collection owner or a metadata updater
+    fn freeze_metadata(&mut self, token_id: TokenId) -> Result<NftEvent, NftError> {
+        self.check_metadata_updater()?;
+        self.check_token_exists(token_id)?;
+        self.frozen_metadata.insert(token_id);
+        Ok(NftEvent::MetadataFrozen { token_id })
+    }
+    /// Send collected mint payments to the collection owner
+    async fn withdraw_proceeds(&mut self) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        let native = self.native_proceeds;
+        if native > 0 {
+            msg::send_bytes(self.owner, [], native).map_err(|_| NftError::PaymentFailed)?;
+            self.native_proceeds = 0;
+        }
+        let mut tokens = 0;
+        if let MintPolicy::Public {
+            price: MintPrice::Token { ft_program_id, .. },
+            ..
+        } = self.config.mint_policy
+        {
+            tokens = core::mem::take(&mut self.token_proceeds);
+            if tokens > 0
+                && transfer_tokens(&ft_program_id, &exec::program_id(), &self.owner, tokens)
+                    .await
+                    .is_err()
+            {
+                self.token_proceeds += tokens;
+                // The native proceeds, if any, are already on their way.
+                if native == 0 {
+                    return Err(NftError::PaymentFailed);
+                }
+                tokens = 0;
+            }
+        }
+        Ok(NftEvent::ProceedsWithdrawn { native, tokens })
+    }
+
+    /// Override the collection royalty for `token_id`
+    fn set_token_royalty(
+        &mut self,
+        token_id: TokenId,
+        royalty: Option<Royalty>,
+    ) -> Result<NftEvent, NftError> {
+        self.check_owner(&self.owner)?;
+        self.check_token_exists(token_id)?;
+        match &royalty {
+            Some(token_royalty) => {
+                check_royalty(token_royalty)?;
+                self.token_royalties.insert(token_id, *token_royalty);
+            }
+            None => {
+                self.token_royalties.remove(&token_id);
+            }
+        }
+        Ok(NftEvent::TokenRoyaltySet { token_id, royalty })
+    }
+    /// Get the royalty receiver and amount owed for selling `token_id` at `sale_price`
+    fn royalty_info(&self, token_id: TokenId, sale_price: u128) -> Option<(ActorId, u128)> {
+        self.check_token_exists(token_id).ok()?;
+        let royalty = self
+            .token_royalties
+            .get(&token_id)
+            .or(self.config.royalty.as_ref())?;
+        let basis_points = royalty.basis_points as u128;
+        let max = MAX_ROYALTY_BASIS_POINTS as u128;
+        // Split the price to avoid overflowing on large sale prices.
+        let amount = sale_price / max * basis_points + sale_price % max * basis_points / max;
+        Some((royalty.receiver, amount))
     }
 
+    /// Checks that `minter` may mint and returns the price it has to pay
+    fn mint_price(&self, minter: &ActorId) -> Result<Option<MintPrice>, NftError> {
+        if minter == &self.owner {
+            return Ok(None);
+        }
+        match &self.config.mint_policy {
+            MintPolicy::OwnerOnly => Err(NftError::AccessDenied),
+            MintPolicy::Allowlist => {
+                if !self.minters.contains(minter) {
+                    return Err(NftError::AccessDenied);
+                }
+                Ok(None)
+            }
+            MintPolicy::Public {
+                price,
+                max_per_wallet,
+            } => {
+                let minted = self
+                    .mints_by_wallet
+                    .get(minter)
+                    .copied()
+                    .unwrap_or_default();
+                if max_per_wallet.is_some_and(|max_per_wallet| minted >= max_per_wallet) {
+                    return Err(NftError::MintLimitReached);
+                }
+                Ok(Some(price.clone()))
+            }
+        }
+    }
     /// Checking the configuration with current contract data
-    fn check_config(&self) {
+    fn check_config(&self, mint_count: u128) -> Result<(), NftError> {
         if let Some(max_mint_count) = self.config.max_mint_count {
-            if max_mint_count <= self.token_metadata_by_id.len() as u128 {
-                panic!(
-                    "Mint impossible because max minting count {} limit exceeded",
-                    max_mint_count
-                );
+            let minted = self.token_metadata_by_id.len() as u128 + self.pending_mints;
+            if max_mint_count < minted.saturating_add(mint_count) {
+                return Err(NftError::MaxMintCountReached);
             }
         }
+        Ok(())
+    }
+    /// Get the owner of the token with indicated `token_id`
+    fn token_owner(&self, token_id: TokenId) -> Result<ActorId, NftError> {
+        self.owner_by_id
+            .get(&token_id)
+            .copied()
+            .ok_or(NftError::TokenDoesNotExist)
+    }
+    /// Checks that `msg::source()` is the collection owner or a metadata updater
+    fn check_metadata_updater(&self) -> Result<(), NftError> {
+        if self.metadata_updaters.contains(&msg::source()) {
+            return Ok(());
+        }
+        self.check_owner(&self.owner)
+    }
+    /// Checks that `token_id` is a minted token or an edition
+    fn check_token_exists(&self, token_id: TokenId) -> Result<(), NftError> {
+        if !self.token_metadata_by_id.contains_key(&token_id) {
+            return Err(NftError::TokenDoesNotExist);
+        }
+        Ok(())
     }
     /// Check for ZERO_ID address
-    fn check_zero_address(&self, account: &ActorId) {
+    fn check_zero_address(&self, account: &ActorId) -> Result<(), NftError> {
         if account == &ZERO_ID {
-            panic!("NonFungibleToken: zero address");
+            return Err(NftError::ZeroAddress);
         }
+        Ok(())
     }
     /// Checks that `msg::source()` is the owner of the token with indicated `token_id`
-    fn check_owner(&self, owner: &ActorId) {
+    fn check_owner(&self, owner: &ActorId) -> Result<(), NftError> {
         if owner != &msg::source() {
-            panic!("NonFungibleToken: access denied");
+            return Err(NftError::AccessDenied);
         }
+        Ok(())
+    }
+    /// Checks that `msg::source()` is the owner or one of the owner's operators
+    fn check_owner_or_operator(&self, owner: &ActorId) -> Result<(), NftError> {
+        if self.is_operator(owner, &msg::source()) {
+            return Ok(());
+        }
+        self.check_owner(owner)
     }
     /// Checks that `msg::source()` is allowed to manage the token with indicated `token_id`
-    fn can_transfer(&self, token_id: TokenId, owner: &ActorId) {
+    fn can_transfer(&self, token_id: TokenId, owner: &ActorId) -> Result<(), NftError> {
         if let Some(approved_accounts) = self.token_approvals.get(&token_id) {
             if approved_accounts == &msg::source() {
-                return;
+                return Ok(());
             }
         }
-        self.check_owner(owner);
+        self.check_owner_or_operator(owner)
+    }
+    /// Checks that `msg::source()` may transfer `token_id` to `to` and returns its owner
+    fn check_transfer(&self, to: &ActorId, token_id: TokenId) -> Result<ActorId, NftError> {
+        let owner = self.token_owner(token_id)?;
+        self.check_unlocked(token_id)?;
+        self.check_transferable()?;
+        self.can_transfer(token_id, &owner)?;
+        self.check_zero_address(to)?;
+        Ok(owner)
+    }
+    /// Checks that `token_id` is not awaiting a safe transfer acknowledgement
+    fn check_unlocked(&self, token_id: TokenId) -> Result<(), NftError> {
+        if self.locked_tokens.contains(&token_id) {
+            return Err(NftError::TokenLocked);
+        }
+        Ok(())
+    }
+    /// Checks that the collection is not soulbound
+    fn check_transferable(&self) -> Result<(), NftError> {
+        if self.collection.soulbound {
+            return Err(NftError::Soulbound);
+        }
+        Ok(())
     }
     /// Check the existence of a approve
-    fn check_approve(&self, token_id: &TokenId) {
+    fn check_approve(&self, token_id: &TokenId) -> Result<(), NftError> {
         if self.token_approvals.contains_key(token_id) {
-            panic!("Approve has already been issued");
+            return Err(NftError::ApprovalAlreadyIssued);
         }
+        Ok(())
     }
 }
 
-#[no_mangle]
-extern fn handle() {
+#[gstd::async_main]
+async fn main() {
     let action: NftAction = msg::load().expect("Could not load NftAction");
     let nft = unsafe { NFT.as_mut().expect("`NFT` is not initialized.") };
     let result = match action {
-        NftAction::Mint { to, token_metadata } => nft.mint(&to, token_metadata),
+        NftAction::Mint { to, token_metadata } => nft.mint(&to, token_metadata).await,
         NftAction::Burn { token_id } => nft.burn(token_id),
         NftAction::Transfer { to, token_id } => nft.transfer(&to, token_id),
         NftAction::Approve { to, token_id } => nft.approve(&to, token_id),
         NftAction::GetOwner { token_id } => nft.owner(token_id),
         NftAction::CheckIfApproved { to, token_id } => nft.is_approved_to(&to, token_id),
+        NftAction::AddMinter { minter } => nft.add_minter(&minter),
+        NftAction::RemoveMinter { minter } => nft.remove_minter(&minter),
+        NftAction::WithdrawProceeds => nft.withdraw_proceeds().await,
+        NftAction::SetTokenRoyalty { token_id, royalty } => {
+            nft.set_token_royalty(token_id, royalty)
+        }
+        NftAction::GetRoyaltyInfo {
+            token_id,
+            sale_price,
+        } => Ok(NftEvent::RoyaltyInfo(
+            nft.royalty_info(token_id, sale_price),
+        )),
+        NftAction::SetApprovalForAll { operator, approved } => {
+            nft.set_approval_for_all(&operator, approved)
+        }
+        NftAction::RevokeApproval { token_id } => nft.revoke_approval(token_id),
+        NftAction::BatchMint { to_and_metadata } => nft.batch_mint(to_and_metadata),
+        NftAction::BatchTransfer { transfers } => nft.batch_transfer(transfers),
+        NftAction::AddVoucherSigner { signer } => nft.add_voucher_signer(&signer),
+        NftAction::RemoveVoucherSigner { signer } => nft.remove_voucher_signer(&signer),
+        NftAction::RedeemVoucher { voucher, signature } => nft.redeem_voucher(voucher, signature),
+        NftAction::AddMetadataUpdater { updater } => nft.add_metadata_updater(&updater),
+        NftAction::RemoveMetadataUpdater { updater } => nft.remove_metadata_updater(&updater),
+        NftAction::UpdateMetadata {
+            token_id,
+            token_metadata,
+        } => nft.update_metadata(token_id, token_metadata),
+        NftAction::FreezeMetadata { token_id } => nft.freeze_metadata(token_id),
+        NftAction::SetUser {
+            token_id,
+            user,
+            expires,
+        } => nft.set_user(token_id, &user, expires),
+        NftAction::Reveal { base_uri } => nft.reveal(base_uri),
+        NftAction::SafeTransfer { to, token_id, data } => {
+            nft.safe_transfer(&to, token_id, data).await
+        }
+        NftAction::CreateEdition { token_metadata } => nft.create_edition(token_metadata),
+        NftAction::MintEdition {
+            token_id,
+            to,
+            amount,
+        } => nft.mint_edition(token_id, &to, amount),
+        NftAction::TransferEditions { from, to, editions } => {
+            nft.transfer_editions(&from, &to, editions)
+        }
+        NftAction::MintWithProof {
+            token_metadata,
+            proof,
+        } => nft.mint_with_proof(token_metadata, proof),
+        NftAction::IsApprovedForAll { owner, operator } => Ok(NftEvent::ApprovalForAll {
+            owner,
+            operator,
+            approved: nft.is_operator(&owner, &operator),
+        }),
     };
-    msg::reply(result, 0).expect("Failed to encode or reply with `NftEvent`.");
+    let value = if result.is_err() { msg::value() } else { 0 };
+    msg::reply(result, value)
+        .expect("Failed to encode or reply with `Result<NftEvent, NftError>`.");
 }
 
 #[no_mangle]
@@ -265,6 +1008,113 @@ extern fn state() {
                 .map(|hashset| hashset.iter().cloned().collect());
             msg::reply(StateReply::OwnerTokens(tokens), 0).expect("Unable to share the state");
         }
+        StateQuery::RoyaltyInfo {
+            token_id,
+            sale_price,
+        } => {
+            let royalty_info = nft.royalty_info(token_id, sale_price);
+            msg::reply(StateReply::RoyaltyInfo(royalty_info), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::IsApprovedForAll { owner, operator } => {
+            let approved = nft.is_operator(&owner, &operator);
+            msg::reply(StateReply::IsApprovedForAll(approved), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::Operators { owner } => {
+            let operators = nft
+                .operators
+                .get(&owner)
+                .map(|operators| operators.iter().cloned().collect())
+                .unwrap_or_default();
+            msg::reply(StateReply::Operators(operators), 0).expect("Unable to share the state");
+        }
+        StateQuery::VoucherSigners => {
+            let signers = nft.voucher_signers.into_iter().collect();
+            msg::reply(StateReply::VoucherSigners(signers), 0).expect("Unable to share the state");
+        }
+        StateQuery::IsVoucherUsed { signer, nonce } => {
+            let used = nft.used_vouchers.contains(&(signer, nonce));
+            msg::reply(StateReply::IsVoucherUsed(used), 0).expect("Unable to share the state");
+        }
+        StateQuery::IsMetadataFrozen { token_id } => {
+            let frozen = nft.frozen_metadata.contains(&token_id);
+            msg::reply(StateReply::IsMetadataFrozen(frozen), 0).expect("Unable to share the state");
+        }
+        StateQuery::UserOf { token_id } => {
+            msg::reply(StateReply::UserOf(nft.user_of(token_id)), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::TokenUri { token_id } => {
+            msg::reply(StateReply::TokenUri(nft.token_uri(token_id)), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::TotalSupply => {
+            msg::reply(StateReply::TotalSupply(nft.all_tokens.len() as u128), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::TokenByIndex { index } => {
+            let token_id = usize::try_from(index)
+                .ok()
+                .and_then(|index| nft.all_tokens.get(index))
+                .copied();
+            msg::reply(StateReply::TokenByIndex(token_id), 0).expect("Unable to share the state");
+        }
+        StateQuery::TokensPaginated { offset, limit } => {
+            let tokens = nft
+                .all_tokens
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect();
+            msg::reply(StateReply::TokensPaginated(tokens), 0).expect("Unable to share the state");
+        }
+        StateQuery::OwnerTokensPaginated {
+            owner,
+            offset,
+            limit,
+        } => {
+            let tokens = nft
+                .tokens_for_owner
+                .get(&owner)
+                .map(|tokens| {
+                    tokens
+                        .iter()
+                        .skip(offset as usize)
+                        .take(limit as usize)
+                        .copied()
+                        .collect()
+                })
+                .unwrap_or_default();
+            msg::reply(StateReply::OwnerTokensPaginated(tokens), 0)
+                .expect("Unable to share the state");
+        }
+        StateQuery::EditionSupply { token_id } => {
+            let supply = nft.edition_supply.get(&token_id).copied();
+            msg::reply(StateReply::EditionSupply(supply), 0).expect("Unable to share the state");
+        }
+        StateQuery::EditionBalance { token_id, owner } => {
+            let balance = nft
+                .edition_balances
+                .get(&(token_id, owner))
+                .copied()
+                .unwrap_or_default();
+            msg::reply(StateReply::EditionBalance(balance), 0).expect("Unable to share the state");
+        }
+        StateQuery::CurrentPhase => {
+            let phase = nft
+                .current_phase()
+                .map(|(index, phase)| (index, phase.clone()));
+            msg::reply(StateReply::CurrentPhase(phase), 0).expect("Unable to share the state");
+        }
+        StateQuery::RemainingAllocation { account } => {
+            let remaining = match nft.current_phase() {
+                Some((index, phase)) => nft.remaining_allocation(index, phase, &account),
+                None => Some(0),
+            };
+            msg::reply(StateReply::RemainingAllocation(remaining), 0)
+                .expect("Unable to share the state");
+        }
     }
 }
 
@@ -279,6 +1129,21 @@ impl From<Nft> for State {
             owner,
             collection,
             config,
+            minters,
+            mints_by_wallet,
+            native_proceeds,
+            token_proceeds,
+            token_royalties,
+            operators,
+            voucher_signers,
+            used_vouchers,
+            metadata_updaters,
+            frozen_metadata,
+            token_users,
+            edition_supply,
+            edition_balances,
+            phase_mints,
+            ..
         } = value;
 
         let owner_by_id = owner_by_id.into_iter().collect();
@@ -301,6 +1166,23 @@ impl From<Nft> for State {
             owner,
             collection,
             config,
+            minters: minters.into_iter().collect(),
+            mints_by_wallet: mints_by_wallet.into_iter().collect(),
+            native_proceeds,
+            token_proceeds,
+            token_royalties: token_royalties.into_iter().collect(),
+            operators: operators
+                .into_iter()
+                .map(|(owner, operators)| (owner, operators.into_iter().collect()))
+                .collect(),
+            voucher_signers: voucher_signers.into_iter().collect(),
+            used_vouchers: used_vouchers.into_iter().collect(),
+            metadata_updaters: metadata_updaters.into_iter().collect(),
+            frozen_metadata: frozen_metadata.into_iter().collect(),
+            token_users: token_users.into_iter().collect(),
+            edition_supply: edition_supply.into_iter().collect(),
+            edition_balances: edition_balances.into_iter().collect(),
+            phase_mints: phase_mints.into_iter().collect(),
         }
     }
 }
\ No newline at end of file
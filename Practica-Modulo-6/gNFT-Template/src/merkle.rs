@@ -0,0 +1,23 @@
+use gstd::ActorId;
+use sha2::{Digest, Sha256};
+
+/// Checks that `account` is a leaf of the merkle tree with `root`.
+///
+/// Leaves are `sha256(account)` and nodes hash their sorted children, so the
+/// proof does not need to tell left from right.
+pub fn verify(root: &[u8; 32], account: &ActorId, proof: &[[u8; 32]]) -> bool {
+    let leaf: [u8; 32] = Sha256::digest(account.as_ref()).into();
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let (left, right) = if node <= *sibling {
+            (node, *sibling)
+        } else {
+            (*sibling, node)
+        };
+        Sha256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into()
+    });
+    &computed == root
+}
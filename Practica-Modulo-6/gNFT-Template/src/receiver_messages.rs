@@ -0,0 +1,31 @@
+use gstd::{msg, prelude::*, ActorId};
+use io::{NftReceiverAction, NftReceiverReply, TokenId};
+
+/// Blocks to wait for the receiver's acknowledgement.
+const ACKNOWLEDGEMENT_TIMEOUT: u32 = 10;
+
+/// Asks `receiver` to accept `token_id`; a missing or unexpected reply is a refusal.
+///
+/// User accounts never reply, so for them this always ends in a timeout.
+pub async fn on_nft_received(
+    receiver: &ActorId,
+    operator: &ActorId,
+    from: &ActorId,
+    token_id: TokenId,
+    data: Vec<u8>,
+) -> bool {
+    let message = NftReceiverAction::OnNftReceived {
+        operator: *operator,
+        from: *from,
+        token_id,
+        data,
+    };
+    let reply = match msg::send_for_reply_as::<_, NftReceiverReply>(*receiver, message, 0, 0)
+        .and_then(|future| future.up_to(Some(ACKNOWLEDGEMENT_TIMEOUT)))
+    {
+        Ok(future) => future.await,
+        Err(_) => return false,
+    };
+
+    matches!(reply, Ok(NftReceiverReply::Received { token_id: received }) if received == token_id)
+}
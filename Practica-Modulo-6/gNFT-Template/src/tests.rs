@@ -0,0 +1,1266 @@
+use gstd::{prelude::*, ActorId};
+use gtest::{Log, Program, RunResult, System, WasmProgram};
+use io::*;
+use schnorrkel::{ExpansionMode, Keypair, MiniSecretKey};
+use sha2::{Digest, Sha256};
+
+const OWNER: u64 = 100;
+const USER: u64 = 101;
+const OTHER: u64 = 102;
+const PRICE: u128 = 10_000_000_000_000;
+
+fn init_nft(system: &System, collection: Collection, config: Config) -> Program<'_> {
+    let program = Program::current(system);
+    let result = program.send(OWNER, InitNft { collection, config });
+    assert!(!result.main_failed());
+    program
+}
+
+fn token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        name: String::from("Item"),
+        ..Default::default()
+    }
+}
+
+fn mint(program: &Program<'_>, to: u64) {
+    let result = program.send(
+        OWNER,
+        NftAction::Mint {
+            to: to.into(),
+            token_metadata: token_metadata(),
+        },
+    );
+    assert!(!result.main_failed());
+}
+
+fn assert_error(result: &RunResult, from: u64, error: NftError) {
+    assert!(result.contains(
+        &Log::builder()
+            .dest(from)
+            .payload(Err::<NftEvent, NftError>(error))
+    ));
+}
+
+fn owner_of(program: &Program<'_>, token_id: TokenId) -> Option<ActorId> {
+    match program
+        .read_state(StateQuery::OwnerById { token_id })
+        .expect("Unable to read state")
+    {
+        StateReply::OwnerById(owner) => owner,
+        _ => unreachable!(),
+    }
+}
+
+/// Receiver of safe transfers that accepts, refuses or ignores every token.
+#[derive(Debug, Clone, Copy)]
+enum Receiver {
+    Accepts,
+    Refuses,
+    Ignores,
+}
+
+impl WasmProgram for Receiver {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let NftReceiverAction::OnNftReceived { token_id, .. } =
+            NftReceiverAction::decode(&mut &payload[..]).map_err(|_| "Unexpected message")?;
+        Ok(match self {
+            Receiver::Accepts => Some(NftReceiverReply::Received { token_id }.encode()),
+            Receiver::Refuses => Some(Vec::new()),
+            Receiver::Ignores => None,
+        })
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+/// Fungible token that fails every transfer.
+#[derive(Debug)]
+struct FailingToken;
+
+impl WasmProgram for FailingToken {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Err("Transfer failed")
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+fn receiver(system: &System, receiver: Receiver) -> ActorId {
+    let program = Program::mock(system, receiver);
+    program.send_bytes(OWNER, b"");
+    program.id().into_bytes().into()
+}
+
+fn signer() -> Keypair {
+    MiniSecretKey::from_bytes(&[7; 32])
+        .expect("Invalid secret key")
+        .expand_to_keypair(ExpansionMode::Ed25519)
+}
+
+fn voucher(signer: &Keypair, nonce: u64) -> MintVoucher {
+    MintVoucher {
+        signer: signer.public.to_bytes().into(),
+        nonce,
+        token_metadata: token_metadata(),
+        price: 0,
+        recipient: None,
+        expires_at: u64::MAX,
+    }
+}
+
+fn sign(program: &Program<'_>, signer: &Keypair, voucher: &MintVoucher) -> [u8; 64] {
+    let program_id = ActorId::from(program.id().into_bytes());
+    signer
+        .sign_simple(b"substrate", &(program_id, voucher).encode())
+        .to_bytes()
+}
+
+#[test]
+fn mint_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let config = Config {
+        max_mint_count: Some(1),
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    system.mint_to(OWNER, PRICE);
+
+    let result = program.send(
+        OWNER,
+        NftAction::Mint {
+            to: ActorId::zero(),
+            token_metadata: token_metadata(),
+        },
+    );
+    assert_error(&result, OWNER, NftError::ZeroAddress);
+
+    let result = program.send(
+        USER,
+        NftAction::Mint {
+            to: USER.into(),
+            token_metadata: token_metadata(),
+        },
+    );
+    assert_error(&result, USER, NftError::AccessDenied);
+
+    // Value attached to a free mint is refunded with the error.
+    let result = program.send_with_value(
+        OWNER,
+        NftAction::Mint {
+            to: USER.into(),
+            token_metadata: token_metadata(),
+        },
+        PRICE,
+    );
+    assert_error(&result, OWNER, NftError::WrongPrice);
+
+    mint(&program, USER);
+    let result = program.send(
+        OWNER,
+        NftAction::Mint {
+            to: USER.into(),
+            token_metadata: token_metadata(),
+        },
+    );
+    assert_error(&result, OWNER, NftError::MaxMintCountReached);
+}
+
+#[test]
+fn public_mint_errors() {
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, 2 * PRICE);
+
+    let config = Config {
+        mint_policy: MintPolicy::Public {
+            price: MintPrice::Native(PRICE),
+            max_per_wallet: Some(1),
+        },
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    let mint_action = || NftAction::Mint {
+        to: USER.into(),
+        token_metadata: token_metadata(),
+    };
+
+    let result = program.send_with_value(USER, mint_action(), PRICE / 2);
+    assert_error(&result, USER, NftError::WrongPrice);
+
+    let result = program.send_with_value(USER, mint_action(), PRICE);
+    assert!(!result.main_failed());
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+
+    let result = program.send_with_value(USER, mint_action(), PRICE);
+    assert_error(&result, USER, NftError::MintLimitReached);
+
+    let result = program.send(
+        USER,
+        NftAction::BatchMint {
+            to_and_metadata: vec![(USER.into(), token_metadata())],
+        },
+    );
+    assert_error(&result, USER, NftError::PaidMintNotSupported);
+}
+
+#[test]
+fn failed_token_payment_mints_nothing() {
+    let system = System::new();
+    system.init_logger();
+
+    let token = Program::mock(&system, FailingToken);
+    token.send_bytes(OWNER, b"");
+    let config = Config {
+        mint_policy: MintPolicy::Public {
+            price: MintPrice::Token {
+                ft_program_id: token.id().into_bytes().into(),
+                amount: 10,
+            },
+            max_per_wallet: Some(1),
+        },
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+
+    // The reserved mint is released, so the second attempt is not over the limit.
+    for _ in 0..2 {
+        let result = program.send(
+            USER,
+            NftAction::Mint {
+                to: USER.into(),
+                token_metadata: token_metadata(),
+            },
+        );
+        assert_error(&result, USER, NftError::PaymentFailed);
+    }
+    assert_eq!(owner_of(&program, 0), None);
+}
+
+#[test]
+fn transfer_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    mint(&program, USER);
+
+    let result = program.send(
+        USER,
+        NftAction::Transfer {
+            to: OTHER.into(),
+            token_id: 2,
+        },
+    );
+    assert_error(&result, USER, NftError::TokenDoesNotExist);
+
+    let result = program.send(
+        OTHER,
+        NftAction::Transfer {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    assert_error(&result, OTHER, NftError::AccessDenied);
+
+    let result = program.send(
+        USER,
+        NftAction::BatchTransfer {
+            transfers: vec![(OTHER.into(), 0), (OTHER.into(), 0)],
+        },
+    );
+    assert_error(&result, USER, NftError::DuplicateToken);
+
+    // A failing batch leaves every token where it was.
+    let result = program.send(
+        USER,
+        NftAction::BatchTransfer {
+            transfers: vec![(OTHER.into(), 0), (ActorId::zero(), 1)],
+        },
+    );
+    assert_error(&result, USER, NftError::ZeroAddress);
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+    assert_eq!(owner_of(&program, 1), Some(USER.into()));
+}
+
+#[test]
+fn enumeration_follows_burns_and_transfers() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    for _ in 0..4 {
+        mint(&program, USER);
+    }
+    let state = |query: StateQuery| -> StateReply {
+        program.read_state(query).expect("Unable to read state")
+    };
+
+    // The last token takes the index of the burnt one.
+    assert!(!program
+        .send(USER, NftAction::Burn { token_id: 1 })
+        .main_failed());
+    assert!(!program
+        .send(
+            USER,
+            NftAction::Transfer {
+                to: OTHER.into(),
+                token_id: 2,
+            },
+        )
+        .main_failed());
+    // A transfer to the owner itself keeps the token listed.
+    assert!(!program
+        .send(
+            USER,
+            NftAction::Transfer {
+                to: USER.into(),
+                token_id: 0,
+            },
+        )
+        .main_failed());
+
+    assert!(matches!(
+        state(StateQuery::TotalSupply),
+        StateReply::TotalSupply(3)
+    ));
+    assert!(matches!(
+        state(StateQuery::TokenByIndex { index: 1 }),
+        StateReply::TokenByIndex(Some(3))
+    ));
+    assert!(matches!(
+        state(StateQuery::TokenByIndex { index: 3 }),
+        StateReply::TokenByIndex(None)
+    ));
+    match state(StateQuery::TokensPaginated {
+        offset: 1,
+        limit: 10,
+    }) {
+        StateReply::TokensPaginated(tokens) => assert_eq!(tokens, vec![3, 2]),
+        _ => unreachable!(),
+    }
+    match state(StateQuery::OwnerTokensPaginated {
+        owner: USER.into(),
+        offset: 0,
+        limit: 10,
+    }) {
+        StateReply::OwnerTokensPaginated(tokens) => assert_eq!(tokens, vec![0, 3]),
+        _ => unreachable!(),
+    }
+    match state(StateQuery::OwnerTokensPaginated {
+        owner: OTHER.into(),
+        offset: 0,
+        limit: 1,
+    }) {
+        StateReply::OwnerTokensPaginated(tokens) => assert_eq!(tokens, vec![2]),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn soulbound_tokens_stay_with_their_owner() {
+    let system = System::new();
+    system.init_logger();
+
+    let collection = Collection {
+        soulbound: true,
+        ..Default::default()
+    };
+    let program = init_nft(&system, collection, Config::default());
+    mint(&program, USER);
+
+    let result = program.send(
+        USER,
+        NftAction::Transfer {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    assert_error(&result, USER, NftError::Soulbound);
+
+    let result = program.send(
+        USER,
+        NftAction::Approve {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    assert_error(&result, USER, NftError::Soulbound);
+
+    let result = program.send(OWNER, NftAction::Burn { token_id: 0 });
+    assert!(result.contains(
+        &Log::builder()
+            .dest(OWNER)
+            .payload(Ok::<NftEvent, NftError>(NftEvent::Burnt { token_id: 0 }))
+    ));
+}
+
+#[test]
+fn safe_transfer_to_accepting_receiver() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let to = receiver(&system, Receiver::Accepts);
+
+    let result = program.send(
+        USER,
+        NftAction::SafeTransfer {
+            to,
+            token_id: 0,
+            data: vec![],
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<NftEvent, NftError>(
+            NftEvent::Transferred {
+                from: USER.into(),
+                to,
+                token_id: 0,
+            }
+        )))
+    );
+    assert_eq!(owner_of(&program, 0), Some(to));
+}
+
+#[test]
+fn safe_transfer_to_refusing_receiver() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let to = receiver(&system, Receiver::Refuses);
+    program.send(
+        USER,
+        NftAction::Approve {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    program.send(
+        USER,
+        NftAction::SetUser {
+            token_id: 0,
+            user: OTHER.into(),
+            expires: u64::MAX,
+        },
+    );
+
+    let result = program.send(
+        USER,
+        NftAction::SafeTransfer {
+            to,
+            token_id: 0,
+            data: vec![],
+        },
+    );
+    assert_error(&result, USER, NftError::SafeTransferRejected);
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+
+    // The approval and the rental survive the refused transfer.
+    match program
+        .read_state(StateQuery::TokenApprovals { token_id: 0 })
+        .expect("Unable to read state")
+    {
+        StateReply::TokenApprovals(approval) => assert_eq!(approval, Some(OTHER.into())),
+        _ => unreachable!(),
+    }
+    match program
+        .read_state(StateQuery::UserOf { token_id: 0 })
+        .expect("Unable to read state")
+    {
+        StateReply::UserOf(user) => assert_eq!(user, Some(OTHER.into())),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn safe_transfer_locks_the_token_until_timeout() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let to = receiver(&system, Receiver::Ignores);
+
+    program.send(
+        USER,
+        NftAction::SafeTransfer {
+            to,
+            token_id: 0,
+            data: vec![],
+        },
+    );
+    assert_eq!(owner_of(&program, 0), Some(to));
+
+    let result = program.send(
+        USER,
+        NftAction::Transfer {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    assert_error(&result, USER, NftError::TokenLocked);
+    let result = program.send(
+        USER,
+        NftAction::Approve {
+            to: OTHER.into(),
+            token_id: 0,
+        },
+    );
+    assert_error(&result, USER, NftError::TokenLocked);
+    let result = program.send(USER, NftAction::Burn { token_id: 0 });
+    assert_error(&result, USER, NftError::TokenLocked);
+
+    // Past the acknowledgement timeout the token goes back to its owner.
+    system.spend_blocks(10);
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+    assert!(!program
+        .send(
+            USER,
+            NftAction::Transfer {
+                to: OTHER.into(),
+                token_id: 0,
+            },
+        )
+        .main_failed());
+    assert_eq!(owner_of(&program, 0), Some(OTHER.into()));
+}
+
+#[test]
+fn approval_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+
+    let approve = || NftAction::Approve {
+        to: OTHER.into(),
+        token_id: 0,
+    };
+    assert!(!program.send(USER, approve()).main_failed());
+    let result = program.send(USER, approve());
+    assert_error(&result, USER, NftError::ApprovalAlreadyIssued);
+
+    let result = program.send(
+        USER,
+        NftAction::SetApprovalForAll {
+            operator: USER.into(),
+            approved: true,
+        },
+    );
+    assert_error(&result, USER, NftError::ApprovalToOwner);
+}
+
+#[test]
+fn operators_manage_every_token_of_the_owner() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    mint(&program, USER);
+    let set_approval_for_all = |approved| NftAction::SetApprovalForAll {
+        operator: OTHER.into(),
+        approved,
+    };
+    let is_approved_for_all = || {
+        let result = program.send(
+            OWNER,
+            NftAction::IsApprovedForAll {
+                owner: USER.into(),
+                operator: OTHER.into(),
+            },
+        );
+        let state = match program
+            .read_state(StateQuery::IsApprovedForAll {
+                owner: USER.into(),
+                operator: OTHER.into(),
+            })
+            .expect("Unable to read state")
+        {
+            StateReply::IsApprovedForAll(approved) => approved,
+            _ => unreachable!(),
+        };
+        let reply = Log::builder().dest(OWNER).payload(Ok::<NftEvent, NftError>(
+            NftEvent::ApprovalForAll {
+                owner: USER.into(),
+                operator: OTHER.into(),
+                approved: state,
+            },
+        ));
+        assert!(result.contains(&reply));
+        state
+    };
+
+    assert!(!is_approved_for_all());
+    assert!(!program.send(USER, set_approval_for_all(true)).main_failed());
+    assert!(is_approved_for_all());
+
+    let result = program.send(
+        OTHER,
+        NftAction::Approve {
+            to: OWNER.into(),
+            token_id: 0,
+        },
+    );
+    assert!(result.contains(
+        &Log::builder()
+            .dest(OTHER)
+            .payload(Ok::<NftEvent, NftError>(NftEvent::Approved {
+                owner: USER.into(),
+                approved_account: OWNER.into(),
+                token_id: 0,
+            }))
+    ));
+    assert!(!program
+        .send(OTHER, NftAction::RevokeApproval { token_id: 0 })
+        .main_failed());
+    match program
+        .read_state(StateQuery::TokenApprovals { token_id: 0 })
+        .expect("Unable to read state")
+    {
+        StateReply::TokenApprovals(approval) => assert_eq!(approval, None),
+        _ => unreachable!(),
+    }
+    assert!(!program
+        .send(
+            OTHER,
+            NftAction::Transfer {
+                to: OTHER.into(),
+                token_id: 0,
+            },
+        )
+        .main_failed());
+    assert_eq!(owner_of(&program, 0), Some(OTHER.into()));
+
+    assert!(!program
+        .send(USER, set_approval_for_all(false))
+        .main_failed());
+    assert!(!is_approved_for_all());
+    let result = program.send(
+        OTHER,
+        NftAction::Transfer {
+            to: OTHER.into(),
+            token_id: 1,
+        },
+    );
+    assert_error(&result, OTHER, NftError::AccessDenied);
+    let result = program.send(
+        OTHER,
+        NftAction::Approve {
+            to: OWNER.into(),
+            token_id: 1,
+        },
+    );
+    assert_error(&result, OTHER, NftError::AccessDenied);
+}
+
+#[test]
+fn invalid_config_aborts_deployment() {
+    let system = System::new();
+    system.init_logger();
+
+    let invalid_configs = [
+        Config {
+            royalty: Some(Royalty {
+                receiver: OWNER.into(),
+                basis_points: 10_001,
+            }),
+            ..Default::default()
+        },
+        Config {
+            sale_phases: vec![SalePhase {
+                start: 10,
+                end: 10,
+                price: 0,
+                max_per_wallet: None,
+                merkle_root: None,
+            }],
+            ..Default::default()
+        },
+    ];
+    for config in invalid_configs {
+        let program = Program::current(&system);
+        let result = program.send(
+            OWNER,
+            InitNft {
+                collection: Collection::default(),
+                config,
+            },
+        );
+        assert!(result.main_failed());
+    }
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let result = program.send(
+        OWNER,
+        NftAction::SetTokenRoyalty {
+            token_id: 0,
+            royalty: Some(Royalty {
+                receiver: OWNER.into(),
+                basis_points: 10_001,
+            }),
+        },
+    );
+    assert_error(&result, OWNER, NftError::RoyaltyTooHigh);
+}
+
+#[test]
+fn token_royalties_override_the_collection_royalty() {
+    let system = System::new();
+    system.init_logger();
+
+    let config = Config {
+        royalty: Some(Royalty {
+            receiver: OWNER.into(),
+            basis_points: 250,
+        }),
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    mint(&program, USER);
+    mint(&program, USER);
+    let royalty_info = |token_id, sale_price| match program
+        .read_state(StateQuery::RoyaltyInfo {
+            token_id,
+            sale_price,
+        })
+        .expect("Unable to read state")
+    {
+        StateReply::RoyaltyInfo(royalty) => royalty,
+        _ => unreachable!(),
+    };
+    let set_token_royalty = |royalty| {
+        let result = program.send(
+            OWNER,
+            NftAction::SetTokenRoyalty {
+                token_id: 1,
+                royalty,
+            },
+        );
+        assert!(!result.main_failed());
+    };
+
+    // 2.5% of 12_345 is 308.625, rounded down.
+    assert_eq!(royalty_info(0, 12_345), Some((OWNER.into(), 308)));
+    assert_eq!(
+        royalty_info(0, u128::MAX),
+        Some((
+            OWNER.into(),
+            8_507_059_173_023_461_586_584_365_185_794_205_286
+        ))
+    );
+    assert_eq!(royalty_info(2, 12_345), None);
+
+    set_token_royalty(Some(Royalty {
+        receiver: OTHER.into(),
+        basis_points: 1_000,
+    }));
+    assert_eq!(royalty_info(1, 12_345), Some((OTHER.into(), 1_234)));
+    assert_eq!(royalty_info(0, 12_345), Some((OWNER.into(), 308)));
+    let result = program.send(
+        USER,
+        NftAction::GetRoyaltyInfo {
+            token_id: 1,
+            sale_price: u128::MAX,
+        },
+    );
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<NftEvent, NftError>(
+            NftEvent::RoyaltyInfo(Some((OTHER.into(), u128::MAX / 10)))
+        )))
+    );
+
+    // Without the override the collection royalty applies again.
+    set_token_royalty(None);
+    assert_eq!(royalty_info(1, 12_345), Some((OWNER.into(), 308)));
+}
+
+#[test]
+fn voucher_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    let signer = signer();
+    let redeem = |from: u64, voucher: MintVoucher, signature: [u8; 64]| {
+        program.send(from, NftAction::RedeemVoucher { voucher, signature })
+    };
+
+    let valid = voucher(&signer, 0);
+    let result = redeem(USER, valid.clone(), sign(&program, &signer, &valid));
+    assert_error(&result, USER, NftError::SignerNotAuthorized);
+
+    let result = program.send(
+        OWNER,
+        NftAction::AddVoucherSigner {
+            signer: valid.signer,
+        },
+    );
+    assert!(!result.main_failed());
+
+    let result = redeem(USER, valid.clone(), [0; 64]);
+    assert_error(&result, USER, NftError::InvalidSignature);
+
+    let result = redeem(USER, valid.clone(), sign(&program, &signer, &valid));
+    assert!(
+        result.contains(&Log::builder().dest(USER).payload(Ok::<NftEvent, NftError>(
+            NftEvent::VoucherRedeemed {
+                signer: valid.signer,
+                nonce: 0,
+                to: USER.into(),
+                token_id: 0,
+            }
+        )))
+    );
+
+    let result = redeem(OTHER, valid.clone(), sign(&program, &signer, &valid));
+    assert_error(&result, OTHER, NftError::VoucherAlreadyRedeemed);
+
+    system.spend_blocks(1);
+    let expired = MintVoucher {
+        expires_at: 0,
+        ..voucher(&signer, 1)
+    };
+    let result = redeem(USER, expired.clone(), sign(&program, &signer, &expired));
+    assert_error(&result, USER, NftError::VoucherExpired);
+
+    let restricted = MintVoucher {
+        recipient: Some(USER.into()),
+        ..voucher(&signer, 2)
+    };
+    let result = redeem(
+        OTHER,
+        restricted.clone(),
+        sign(&program, &signer, &restricted),
+    );
+    assert_error(&result, OTHER, NftError::WrongRecipient);
+}
+
+#[test]
+fn metadata_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+
+    // Holding the token does not allow freezing its metadata.
+    let result = program.send(USER, NftAction::FreezeMetadata { token_id: 0 });
+    assert_error(&result, USER, NftError::AccessDenied);
+    assert!(!program
+        .send(OWNER, NftAction::FreezeMetadata { token_id: 0 })
+        .main_failed());
+    let result = program.send(
+        OWNER,
+        NftAction::UpdateMetadata {
+            token_id: 0,
+            token_metadata: token_metadata(),
+        },
+    );
+    assert_error(&result, OWNER, NftError::MetadataFrozen);
+
+    let result = program.send(
+        OWNER,
+        NftAction::Reveal {
+            base_uri: String::from("ipfs://collection/"),
+        },
+    );
+    assert_error(&result, OWNER, NftError::AlreadyRevealed);
+}
+
+#[test]
+fn metadata_updaters_update_and_freeze_metadata() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let token_metadata = TokenMetadata {
+        attributes: vec![(String::from("level"), String::from("3"))],
+        ..token_metadata()
+    };
+    let update = || NftAction::UpdateMetadata {
+        token_id: 0,
+        token_metadata: token_metadata.clone(),
+    };
+
+    let result = program.send(OTHER, update());
+    assert_error(&result, OTHER, NftError::AccessDenied);
+
+    program.send(
+        OWNER,
+        NftAction::AddMetadataUpdater {
+            updater: OTHER.into(),
+        },
+    );
+    let result = program.send(OTHER, update());
+    assert!(result.contains(
+        &Log::builder()
+            .dest(OTHER)
+            .payload(Ok::<NftEvent, NftError>(NftEvent::MetadataUpdated {
+                token_id: 0,
+                token_metadata: token_metadata.clone(),
+            }))
+    ));
+    match program
+        .read_state(StateQuery::TokenMetadata { token_id: 0 })
+        .expect("Unable to read state")
+    {
+        StateReply::TokenMetadata(metadata) => assert_eq!(
+            metadata.map(|metadata| metadata.attributes),
+            Some(token_metadata.attributes.clone())
+        ),
+        _ => unreachable!(),
+    }
+
+    assert!(!program
+        .send(OTHER, NftAction::FreezeMetadata { token_id: 0 })
+        .main_failed());
+    let result = program.send(OTHER, update());
+    assert_error(&result, OTHER, NftError::MetadataFrozen);
+
+    program.send(
+        OWNER,
+        NftAction::RemoveMetadataUpdater {
+            updater: OTHER.into(),
+        },
+    );
+    let result = program.send(OTHER, NftAction::FreezeMetadata { token_id: 0 });
+    assert_error(&result, OTHER, NftError::AccessDenied);
+}
+
+#[test]
+fn token_uris_follow_the_reveal() {
+    let system = System::new();
+    system.init_logger();
+
+    let collection = Collection {
+        placeholder_uri: Some(String::from("ipfs://hidden")),
+        ..Default::default()
+    };
+    let program = init_nft(&system, collection, Config::default());
+    mint(&program, USER);
+    program.send(
+        OWNER,
+        NftAction::Mint {
+            to: USER.into(),
+            token_metadata: TokenMetadata {
+                uri_suffix: Some(String::from("one.json")),
+                ..token_metadata()
+            },
+        },
+    );
+    let token_uri = |token_id| match program
+        .read_state(StateQuery::TokenUri { token_id })
+        .expect("Unable to read state")
+    {
+        StateReply::TokenUri(uri) => uri,
+        _ => unreachable!(),
+    };
+    assert_eq!(token_uri(1).as_deref(), Some("ipfs://hidden"));
+
+    let result = program.send(
+        OWNER,
+        NftAction::Reveal {
+            base_uri: String::new(),
+        },
+    );
+    assert_error(&result, OWNER, NftError::EmptyBaseUri);
+
+    program.send(
+        OWNER,
+        NftAction::Reveal {
+            base_uri: String::from("ipfs://collection/"),
+        },
+    );
+    assert_eq!(token_uri(0).as_deref(), Some("ipfs://collection/0"));
+    assert_eq!(token_uri(1).as_deref(), Some("ipfs://collection/one.json"));
+}
+
+#[test]
+fn rental_expiry_must_be_in_the_future() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+
+    let result = program.send(
+        USER,
+        NftAction::SetUser {
+            token_id: 0,
+            user: OTHER.into(),
+            expires: 0,
+        },
+    );
+    assert_error(&result, USER, NftError::InvalidExpiry);
+}
+
+#[test]
+fn edition_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    mint(&program, USER);
+    let result = program.send(
+        OWNER,
+        NftAction::CreateEdition {
+            token_metadata: token_metadata(),
+        },
+    );
+    assert!(!result.main_failed());
+    let mint_edition = |token_id, amount| NftAction::MintEdition {
+        token_id,
+        to: USER.into(),
+        amount,
+    };
+
+    let result = program.send(OWNER, mint_edition(0, 1));
+    assert_error(&result, OWNER, NftError::EditionDoesNotExist);
+
+    assert!(!program
+        .send(OWNER, mint_edition(1, u128::MAX))
+        .main_failed());
+    let result = program.send(OWNER, mint_edition(1, 1));
+    assert_error(&result, OWNER, NftError::ArithmeticOverflow);
+
+    let result = program.send(
+        OTHER,
+        NftAction::TransferEditions {
+            from: OTHER.into(),
+            to: USER.into(),
+            editions: vec![(1, 1)],
+        },
+    );
+    assert_error(&result, OTHER, NftError::InsufficientEditionBalance);
+}
+
+#[test]
+fn editions_share_royalties_and_frozen_metadata() {
+    let system = System::new();
+    system.init_logger();
+
+    let program = init_nft(&system, Collection::default(), Config::default());
+    program.send(
+        OWNER,
+        NftAction::CreateEdition {
+            token_metadata: token_metadata(),
+        },
+    );
+
+    let result = program.send(
+        OWNER,
+        NftAction::SetTokenRoyalty {
+            token_id: 0,
+            royalty: Some(Royalty {
+                receiver: OWNER.into(),
+                basis_points: 500,
+            }),
+        },
+    );
+    assert!(!result.main_failed());
+    match program
+        .read_state(StateQuery::RoyaltyInfo {
+            token_id: 0,
+            sale_price: 10_000,
+        })
+        .expect("Unable to read state")
+    {
+        StateReply::RoyaltyInfo(royalty) => assert_eq!(royalty, Some((OWNER.into(), 500))),
+        _ => unreachable!(),
+    }
+
+    let result = program.send(USER, NftAction::FreezeMetadata { token_id: 0 });
+    assert_error(&result, USER, NftError::AccessDenied);
+    assert!(!program
+        .send(OWNER, NftAction::FreezeMetadata { token_id: 0 })
+        .main_failed());
+    let result = program.send(
+        OWNER,
+        NftAction::UpdateMetadata {
+            token_id: 0,
+            token_metadata: token_metadata(),
+        },
+    );
+    assert_error(&result, OWNER, NftError::MetadataFrozen);
+}
+
+#[test]
+fn sale_phase_errors() {
+    let system = System::new();
+    system.init_logger();
+
+    let phase = |start, merkle_root| SalePhase {
+        start,
+        end: u64::MAX,
+        price: 0,
+        max_per_wallet: Some(1),
+        merkle_root,
+    };
+    let mint_with_proof = || NftAction::MintWithProof {
+        token_metadata: token_metadata(),
+        proof: vec![],
+    };
+
+    let config = Config {
+        sale_phases: vec![phase(u64::MAX - 1, None)],
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    let result = program.send(USER, mint_with_proof());
+    assert_error(&result, USER, NftError::NoActiveSalePhase);
+
+    // A single allowed account is the root of its own tree.
+    let merkle_root: [u8; 32] = Sha256::digest(ActorId::from(USER).as_ref()).into();
+    let config = Config {
+        sale_phases: vec![phase(0, Some(merkle_root))],
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+
+    let result = program.send(OTHER, mint_with_proof());
+    assert_error(&result, OTHER, NftError::NotInAllowlist);
+
+    let result = program.send(USER, mint_with_proof());
+    assert!(!result.main_failed());
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+
+    let result = program.send(USER, mint_with_proof());
+    assert_error(&result, USER, NftError::MintLimitReached);
+}
+
+#[test]
+fn allowlist_proofs_and_phase_queries() {
+    let system = System::new();
+    system.init_logger();
+
+    let third = 103;
+    let leaf =
+        |account: u64| -> [u8; 32] { Sha256::digest(ActorId::from(account).as_ref()).into() };
+    let node = |a: [u8; 32], b: [u8; 32]| -> [u8; 32] {
+        let (left, right) = if a <= b { (a, b) } else { (b, a) };
+        Sha256::new()
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .into()
+    };
+    // USER and OTHER share a node whose sibling is the leaf of `third`.
+    let users = node(leaf(USER), leaf(OTHER));
+    let merkle_root = node(users, leaf(third));
+    let phase = |start, merkle_root| SalePhase {
+        start,
+        end: u64::MAX,
+        price: 0,
+        max_per_wallet: Some(2),
+        merkle_root,
+    };
+    let config = Config {
+        sale_phases: vec![phase(u64::MAX - 1, None), phase(0, Some(merkle_root))],
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    let mint_with_proof = |from: u64, proof: Vec<[u8; 32]>| {
+        program.send(
+            from,
+            NftAction::MintWithProof {
+                token_metadata: token_metadata(),
+                proof,
+            },
+        )
+    };
+    let remaining_allocation = |account: u64| match program
+        .read_state(StateQuery::RemainingAllocation {
+            account: account.into(),
+        })
+        .expect("Unable to read state")
+    {
+        StateReply::RemainingAllocation(remaining) => remaining,
+        _ => unreachable!(),
+    };
+
+    match program
+        .read_state(StateQuery::CurrentPhase)
+        .expect("Unable to read state")
+    {
+        StateReply::CurrentPhase(Some((index, phase))) => {
+            assert_eq!(index, 1);
+            assert_eq!(phase.merkle_root, Some(merkle_root));
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(remaining_allocation(USER), Some(2));
+
+    // A wrong sibling leads to another root.
+    let result = mint_with_proof(USER, vec![leaf(OWNER), leaf(third)]);
+    assert_error(&result, USER, NftError::NotInAllowlist);
+    let result = mint_with_proof(OWNER, vec![leaf(OTHER), leaf(third)]);
+    assert_error(&result, OWNER, NftError::NotInAllowlist);
+
+    assert!(!mint_with_proof(USER, vec![leaf(OTHER), leaf(third)]).main_failed());
+    assert!(!mint_with_proof(third, vec![users]).main_failed());
+    assert_eq!(owner_of(&program, 0), Some(USER.into()));
+    assert_eq!(owner_of(&program, 1), Some(third.into()));
+    assert_eq!(remaining_allocation(USER), Some(1));
+    assert_eq!(remaining_allocation(OTHER), Some(2));
+
+    // Outside of every phase nothing can be minted.
+    let config = Config {
+        sale_phases: vec![phase(u64::MAX - 1, None)],
+        ..Default::default()
+    };
+    let program = init_nft(&system, Collection::default(), config);
+    assert!(matches!(
+        program
+            .read_state(StateQuery::CurrentPhase)
+            .expect("Unable to read state"),
+        StateReply::CurrentPhase(None)
+    ));
+    match program
+        .read_state(StateQuery::RemainingAllocation {
+            account: USER.into(),
+        })
+        .expect("Unable to read state")
+    {
+        StateReply::RemainingAllocation(remaining) => assert_eq!(remaining, Some(0)),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,20 @@
+use gstd::{exec, prelude::*};
+use io::MintVoucher;
+use schnorrkel::{PublicKey, Signature};
+
+/// Signing context used by Substrate wallets.
+const SIGNING_CONTEXT: &[u8] = b"substrate";
+
+/// Checks that `voucher.signer` signed `voucher` for this program.
+pub fn is_signed(voucher: &MintVoucher, signature: &[u8; 64]) -> bool {
+    let Ok(public_key) = PublicKey::from_bytes(voucher.signer.as_ref()) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_bytes(signature) else {
+        return false;
+    };
+    let message = (exec::program_id(), voucher).encode();
+    public_key
+        .verify_simple(SIGNING_CONTEXT, &message, &signature)
+        .is_ok()
+}